@@ -1,7 +1,12 @@
-use crate::cli::Config;
-use anyhow::Result;
+use crate::cache::{BuildCache, CacheStatus, KeyHasher};
+use crate::config::Config;
+use crate::deps::{self, Dependency};
+use crate::diagnostics::{self, Diagnostic, ToolError};
+use crate::target::{self, Arch, ObjectFormat, Os, Target};
+use anyhow::{Context, Result};
 use glob::glob;
 use log::{debug, info};
+use std::path::{Component, Path, PathBuf};
 use std::process::Command;
 
 /// Expand glob patterns in input files to concrete file paths.
@@ -32,6 +37,292 @@ pub fn expand_globs(inputs: &[String]) -> Result<Vec<String>>
     Ok(expanded)
 }
 
+/// An assembler tool family and how to invoke it.
+///
+/// Each implementation knows the command-line layout of one tool and how to
+/// translate a RASM target into the flags that select its object format.
+pub trait Assembler: Sync 
+{
+    /// Short name of the tool family, used in logs and error messages.
+    fn name(&self) -> &'static str;
+
+    /// Flags selecting the object format and architecture for `target`.
+    fn target_flags(&self, target: &Target) -> Result<Vec<String>>;
+
+    /// Whether the user's flags already select an object format, in which
+    /// case the target flags are left out.
+    fn selects_target(&self, flags: &[String]) -> bool;
+
+    /// Flags that emit debug information for `target`.
+    fn debug_flags(&self, target: &Target) -> Vec<String>;
+
+    /// Build the command that assembles `input` into `output`.
+    fn command(&self, program: &str, input: &str, output: &str, flags: &[String]) -> Command;
+}
+
+/// GNU as, and the Apple/clang `as` driver.
+pub struct Gas;
+
+/// The Netwide Assembler.
+pub struct Nasm;
+
+/// The Yasm modular assembler (NASM-compatible command line).
+pub struct Yasm;
+
+/// LLVM's machine code playground, driven by an explicit triple.
+pub struct LlvmMc;
+
+/// The flat assembler; the output format is chosen in the source.
+pub struct Fasm;
+
+impl Assembler for Gas 
+{
+    fn name(&self) -> &'static str 
+    {
+        "gas"
+    }
+
+    fn target_flags(&self, target: &Target) -> Result<Vec<String>> 
+    {
+        let flags: &[&str] = match (target.os, target.arch) 
+        {
+            (Os::Macos, Arch::Aarch64) => &["-arch", "arm64"],
+            (Os::Macos, Arch::X86_64) => &["-arch", "x86_64"],
+            (Os::Macos, Arch::X86) => &["-arch", "i386"],
+            (_, Arch::X86_64) => &["--64"],
+            (_, Arch::X86) => &["--32"],
+            _ => &[],
+        };
+        Ok(flags.iter().map(|f| f.to_string()).collect())
+    }
+
+    fn selects_target(&self, flags: &[String]) -> bool 
+    {
+        flags.iter().any(|f| matches!(f.as_str(), "--32" | "--64" | "--x32" | "-arch"))
+    }
+
+    fn debug_flags(&self, _target: &Target) -> Vec<String> 
+    {
+        vec!["-g".to_string()]
+    }
+
+    fn command(&self, program: &str, input: &str, output: &str, flags: &[String]) -> Command 
+    {
+        let mut cmd = Command::new(program);
+        cmd.arg(input).arg("-o").arg(output).args(flags);
+        cmd
+    }
+}
+
+/// Object format name shared by NASM and Yasm.
+fn nasm_format(tool: &str, target: &Target) -> Result<Vec<String>> 
+{
+    let format = match (target.format, target.arch) 
+    {
+        (ObjectFormat::Elf, Arch::X86_64) => "elf64",
+        (ObjectFormat::Elf, Arch::X86) => "elf32",
+        (ObjectFormat::MachO, Arch::X86_64) => "macho64",
+        (ObjectFormat::MachO, Arch::X86) => "macho32",
+        (ObjectFormat::Coff, Arch::X86_64) => "win64",
+        (ObjectFormat::Coff, Arch::X86) => "win32",
+        _ => {
+            return Err(anyhow::anyhow!(
+                "{} cannot assemble for target {}",
+                tool,
+                target.triple
+            ))
+        }
+    };
+    Ok(vec!["-f".to_string(), format.to_string()])
+}
+
+fn selects_nasm_format(flags: &[String]) -> bool 
+{
+    flags.iter().any(|f| f.starts_with("-f") || f.starts_with("--oformat"))
+}
+
+impl Assembler for Nasm 
+{
+    fn name(&self) -> &'static str 
+    {
+        "nasm"
+    }
+
+    fn target_flags(&self, target: &Target) -> Result<Vec<String>> 
+    {
+        nasm_format(self.name(), target)
+    }
+
+    fn selects_target(&self, flags: &[String]) -> bool 
+    {
+        selects_nasm_format(flags)
+    }
+
+    fn debug_flags(&self, target: &Target) -> Vec<String> 
+    {
+        match target.format 
+        {
+            ObjectFormat::Elf => vec!["-g".to_string(), "-F".to_string(), "dwarf".to_string()],
+            _ => vec!["-g".to_string()],
+        }
+    }
+
+    fn command(&self, program: &str, input: &str, output: &str, flags: &[String]) -> Command 
+    {
+        let mut cmd = Command::new(program);
+        cmd.args(flags).arg(input).arg("-o").arg(output);
+        cmd
+    }
+}
+
+impl Assembler for Yasm 
+{
+    fn name(&self) -> &'static str 
+    {
+        "yasm"
+    }
+
+    fn target_flags(&self, target: &Target) -> Result<Vec<String>> 
+    {
+        nasm_format(self.name(), target)
+    }
+
+    fn selects_target(&self, flags: &[String]) -> bool 
+    {
+        selects_nasm_format(flags)
+    }
+
+    fn debug_flags(&self, target: &Target) -> Vec<String> 
+    {
+        let format = if target.format == ObjectFormat::Coff { "cv8" } else { "dwarf2" };
+        vec!["-g".to_string(), format.to_string()]
+    }
+
+    fn command(&self, program: &str, input: &str, output: &str, flags: &[String]) -> Command 
+    {
+        let mut cmd = Command::new(program);
+        cmd.args(flags).arg(input).arg("-o").arg(output);
+        cmd
+    }
+}
+
+impl Assembler for LlvmMc 
+{
+    fn name(&self) -> &'static str 
+    {
+        "llvm-mc"
+    }
+
+    fn target_flags(&self, target: &Target) -> Result<Vec<String>> 
+    {
+        Ok(vec![format!("-triple={}", target.triple)])
+    }
+
+    fn selects_target(&self, flags: &[String]) -> bool 
+    {
+        flags.iter().any(|f| f.starts_with("-triple") || f.starts_with("--triple"))
+    }
+
+    fn debug_flags(&self, _target: &Target) -> Vec<String> 
+    {
+        vec!["-g".to_string()]
+    }
+
+    fn command(&self, program: &str, input: &str, output: &str, flags: &[String]) -> Command 
+    {
+        let mut cmd = Command::new(program);
+        // Without -filetype=obj llvm-mc echoes assembly text instead.
+        cmd.arg("-filetype=obj").args(flags).arg(input).arg("-o").arg(output);
+        cmd
+    }
+}
+
+impl Assembler for Fasm 
+{
+    fn name(&self) -> &'static str 
+    {
+        "fasm"
+    }
+
+    fn target_flags(&self, _target: &Target) -> Result<Vec<String>> 
+    {
+        Ok(Vec::new())
+    }
+
+    fn selects_target(&self, _flags: &[String]) -> bool 
+    {
+        // `format ELF64` and friends live in the source file.
+        true
+    }
+
+    fn debug_flags(&self, _target: &Target) -> Vec<String> 
+    {
+        // fasm has no debug information output.
+        Vec::new()
+    }
+
+    fn command(&self, program: &str, input: &str, output: &str, flags: &[String]) -> Command 
+    {
+        let mut cmd = Command::new(program);
+        cmd.args(flags).arg(input).arg(output);
+        cmd
+    }
+}
+
+/// Pick the backend for an assembler program from its file name,
+/// so that "nasm", "/opt/bin/yasm" and "x86_64-linux-gnu-as" all resolve.
+/// Unrecognized programs are assumed to take GNU as arguments.
+pub fn backend_for(program: &str) -> &'static dyn Assembler 
+{
+    let name = Path::new(program)
+        .file_name()
+        .map(|n| n.to_string_lossy().to_ascii_lowercase())
+        .unwrap_or_default();
+    let name = name.strip_suffix(".exe").unwrap_or(&name);
+
+    if name.ends_with("nasm") 
+    {
+        &Nasm
+    } else if name.ends_with("yasm") {
+        &Yasm
+    } else if name.contains("llvm-mc") {
+        &LlvmMc
+    } else if name.ends_with("fasm") {
+        &Fasm
+    } else {
+        &Gas
+    }
+}
+
+/// Get the object file path produced for an assembly source file.
+///
+/// Objects are placed in the build directory, mirroring the source tree so
+/// that `a/x.s` and `b/x.s` map to distinct objects. Absolute sources under
+/// the working directory are mapped relative to it; other absolute sources
+/// keep their full path below the build directory.
+pub fn object_path(input: &str, config: &Config) -> String 
+{
+    let path = Path::new(input);
+    let cwd = std::env::current_dir().unwrap_or_default();
+    let relative = path.strip_prefix(&cwd).unwrap_or(path);
+
+    let mut obj = PathBuf::from(config.build_dir());
+    for component in relative.components() 
+    {
+        match component 
+        {
+            Component::Normal(part) => obj.push(part),
+            // Drop "./", the root and any drive prefix so that absolute
+            // paths stay inside the build directory.
+            Component::CurDir | Component::RootDir | Component::Prefix(_) => {}
+            Component::ParentDir => obj.push("__"),
+        }
+    }
+    let mut name = obj.into_os_string();
+    name.push(".o");
+    PathBuf::from(name).to_string_lossy().to_string()
+}
+
 /// Assemble a single assembly source file into an object file.
 /// 
 /// # Arguments
@@ -39,8 +330,8 @@ pub fn expand_globs(inputs: &[String]) -> Result<Vec<String>>
 /// * `config` - Configuration containing assembler settings
 /// 
 /// # Returns
-/// Path to the generated object file
-pub fn assemble(input: &str, config: &Config) -> Result<String> 
+/// Path to the generated object file and the warnings the assembler printed
+pub fn assemble(input: &str, config: &Config) -> Result<(String, Vec<Diagnostic>)> 
 {
     // Validate that glob patterns are handled at a higher level
     // Individual assemble calls should receive concrete file paths
@@ -54,15 +345,22 @@ pub fn assemble(input: &str, config: &Config) -> Result<String>
     
     let input_path = input.to_string();
 
-    let obj_file = format!("{}.o", input_path);
-    let mut cmd = Command::new(&config.assembler);
-    cmd.arg(&input_path)
-       .arg("-o")
-       .arg(&obj_file);
-    for flag in &config.assembler_flags 
+    let obj_file = object_path(&input_path, config);
+    let backend = backend_for(&config.assembler);
+    let target = target::parse(&config.target)?;
+
+    let mut flags = Vec::new();
+    if !backend.selects_target(&config.assembler_flags) 
     {
-        cmd.arg(flag);
+        flags = backend.target_flags(target)?;
     }
+    if config.debug 
+    {
+        flags.extend(backend.debug_flags(target));
+    }
+    flags.extend(config.assembler_flags.iter().cloned());
+
+    let mut cmd = backend.command(&config.assembler, &input_path, &obj_file, &flags);
     if config.verbose || config.dry_run 
     {
         info!("Assembling {} -> {}", input_path, obj_file);
@@ -70,23 +368,149 @@ pub fn assemble(input: &str, config: &Config) -> Result<String>
     }
     if config.dry_run 
     {
-        return Ok(obj_file);
+        return Ok((obj_file, Vec::new()));
+    }
+    if let Some(parent) = Path::new(&obj_file).parent() 
+    {
+        std::fs::create_dir_all(parent)?;
     }
     let output = cmd.output()?;
-    if !output.status.success() 
+    // fasm reports errors on stdout, and its progress there on success.
+    let failed = !output.status.success();
+    let messages = if failed && output.stderr.is_empty() { &output.stdout } else { &output.stderr };
+    let messages = diagnostics::parse_assembler(&String::from_utf8_lossy(messages));
+    if failed 
     {
-        return Err(anyhow::anyhow!(
-            "Assembler failed:\n{}",
-            String::from_utf8_lossy(&output.stderr)
-        ));
+        return Err(ToolError 
+        {
+            tool: format!("Assembler ({})", backend.name()),
+            diagnostics: messages,
+        }
+        .into());
+    }
+    Ok((obj_file, diagnostics::as_warnings(messages)))
+}
+
+/// Compute the cache key for assembling `input` with the current configuration.
+///
+/// The key covers the source path and bytes, the contents of every included
+/// file, the assembler binary and version, the assembler flags, the target and
+/// whether debug information is emitted.
+/// The path is included because assemblers embed it in debug information.
+pub fn cache_key(input: &str, deps: &[Dependency], config: &Config, cache: &BuildCache) -> Result<String> 
+{
+    let source = std::fs::read(input)
+        .with_context(|| format!("Failed to read source file: {}", input))?;
+
+    let mut hasher = KeyHasher::new();
+    hasher
+        .field(input)
+        .field(&source)
+        .field(cache.tool_identity(&config.assembler))
+        .field(&config.target)
+        .field([config.debug as u8]);
+    for flag in &config.assembler_flags 
+    {
+        hasher.field(flag);
+    }
+    for dep in deps 
+    {
+        match dep 
+        {
+            Dependency::Found(path) => {
+                let contents = std::fs::read(path)
+                    .with_context(|| format!("Failed to read dependency: {}", path.display()))?;
+                hasher.field(path.to_string_lossy().as_bytes()).field(&contents);
+            }
+            Dependency::Missing(path) => {
+                hasher.field("missing").field(path);
+            }
+        }
+    }
+    Ok(hasher.finish())
+}
+
+/// An object file produced by `assemble_cached`.
+pub struct Assembled 
+{
+    pub object: String,
+    pub status: CacheStatus,
+    /// Warnings from assembling the source, replayed when the object is reused.
+    pub warnings: Vec<Diagnostic>,
+}
+
+/// Assemble a source file, reusing the existing object or a cached copy
+/// when one was built from identical inputs.
+/// 
+/// # Returns
+/// The object file, how it was obtained and its warnings
+pub fn assemble_cached(input: &str, config: &Config, cache: &BuildCache) -> Result<Assembled> 
+{
+    let obj_file = object_path(input, config);
+    let deps = deps::scan(Path::new(input), &deps::include_dirs(config));
+    let key = cache_key(input, &deps, config, cache)?;
+    cache.record_dependencies(&obj_file, &deps);
+
+    if !config.force 
+    {
+        if cache.is_fresh(&obj_file, &key) 
+        {
+            debug!("Object file is up to date: {}", obj_file);
+            return Ok(Assembled { object: obj_file, status: CacheStatus::UpToDate, warnings: cache.warnings(&key) });
+        }
+        if !config.dry_run && cache.restore(&obj_file, &key) 
+        {
+            if config.verbose 
+            {
+                info!("Restored {} from cache", obj_file);
+            }
+            return Ok(Assembled { object: obj_file, status: CacheStatus::Hit, warnings: cache.warnings(&key) });
+        }
+    }
+
+    let (obj_file, warnings) = assemble(input, config)?;
+    if !config.dry_run 
+    {
+        cache.store(&obj_file, &key);
+        cache.record_warnings(&key, &warnings);
     }
-    Ok(obj_file)
+    Ok(Assembled { object: obj_file, status: CacheStatus::Miss, warnings })
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn test_config() -> Config {
+        Config {
+            input_files: vec![],
+            output_file: "test.out".to_string(),
+            extra_flags: vec![],
+            assembler: "as".to_string(),
+            assembler_flags: vec![],
+            linker: None,
+            archiver: None,
+            target: "x86_64".to_string(),
+            verbose: false,
+            dry_run: false,
+            deny_warnings: false,
+            build_dir: None,
+            profile: "dev".to_string(),
+            profiles: Default::default(),
+            debug: false,
+            strip: false,
+            force: false,
+            keep_going: false,
+            message_format: crate::events::MessageFormat::Human,
+            reports: Vec::new(),
+            summary_file: None,
+            config_file: None,
+            color: "auto".to_string(),
+            jobs: None,
+            artifact_tables: Vec::new(),
+        }
+    }
+
     #[test]
     fn test_expand_globs_no_patterns() {
         let inputs = vec!["file1.s".to_string(), "file2.s".to_string()];
@@ -103,22 +527,8 @@ mod tests {
 
     #[test]
     fn test_assemble_rejects_glob_pattern() {
-        let config = Config {
-            input_files: vec![],
-            output_file: "test.out".to_string(),
-            extra_flags: vec![],
-            assembler: "as".to_string(),
-            assembler_flags: vec![],
-            target: "x86_64".to_string(),
-            verbose: false,
-            dry_run: false,
-            clean: false,
-            config_file: None,
-            watch: false,
-            color: "auto".to_string(),
-            completions: None,
-        };
-        
+        let config = test_config();
+
         let result = assemble("*.s", &config);
         assert!(result.is_err());
         assert!(result.unwrap_err().to_string().contains("Glob patterns"));
@@ -126,24 +536,69 @@ mod tests {
 
     #[test]
     fn test_assemble_dry_run() {
-        let config = Config {
-            input_files: vec![],
-            output_file: "test.out".to_string(),
-            extra_flags: vec![],
-            assembler: "as".to_string(),
-            assembler_flags: vec![],
-            target: "x86_64".to_string(),
-            verbose: false,
-            dry_run: true,
-            clean: false,
-            config_file: None,
-            watch: false,
-            color: "auto".to_string(),
-            completions: None,
-        };
-        
+        let mut config = test_config();
+        config.dry_run = true;
+
         let result = assemble("test.s", &config);
         assert!(result.is_ok());
-        assert_eq!(result.unwrap(), "test.s.o");
+        assert_eq!(result.unwrap().0, "target/rasm/dev/test.s.o");
+    }
+
+    fn args(cmd: &Command) -> Vec<String> {
+        cmd.get_args().map(|a| a.to_string_lossy().to_string()).collect()
+    }
+
+    #[test]
+    fn test_backend_detection() {
+        assert_eq!(backend_for("as").name(), "gas");
+        assert_eq!(backend_for("aarch64-linux-gnu-as").name(), "gas");
+        assert_eq!(backend_for("/usr/local/bin/nasm").name(), "nasm");
+        assert_eq!(backend_for("yasm").name(), "yasm");
+        assert_eq!(backend_for("llvm-mc-18").name(), "llvm-mc");
+        assert_eq!(backend_for("FASM.EXE").name(), "fasm");
+    }
+
+    #[test]
+    fn test_nasm_target_flags() {
+        let linux = target::parse("x86_64-linux").unwrap();
+        let macos = target::parse("x86_64-macos").unwrap();
+        let windows = target::parse("i686-pc-windows-gnu").unwrap();
+        assert_eq!(Nasm.target_flags(linux).unwrap(), vec!["-f", "elf64"]);
+        assert_eq!(Yasm.target_flags(macos).unwrap(), vec!["-f", "macho64"]);
+        assert_eq!(Nasm.target_flags(windows).unwrap(), vec!["-f", "win32"]);
+        assert!(Nasm.target_flags(target::parse("arm64").unwrap()).is_err());
+        assert!(Nasm.selects_target(&["-felf32".to_string()]));
+    }
+
+    #[test]
+    fn test_backend_command_layouts() {
+        let flags = vec!["-g".to_string()];
+        assert_eq!(args(&Gas.command("as", "a.s", "a.o", &flags)), vec!["a.s", "-o", "a.o", "-g"]);
+        assert_eq!(args(&Nasm.command("nasm", "a.asm", "a.o", &flags)), vec!["-g", "a.asm", "-o", "a.o"]);
+        assert_eq!(
+            args(&LlvmMc.command("llvm-mc", "a.s", "a.o", &flags)),
+            vec!["-filetype=obj", "-g", "a.s", "-o", "a.o"]
+        );
+        assert_eq!(args(&Fasm.command("fasm", "a.asm", "a.o", &flags)), vec!["-g", "a.asm", "a.o"]);
+
+        let aarch64 = target::parse("aarch64-linux").unwrap();
+        assert_eq!(LlvmMc.target_flags(aarch64).unwrap(), vec!["-triple=aarch64-unknown-linux-gnu"]);
+    }
+
+    #[test]
+    fn test_object_path_mirrors_source_tree() {
+        let mut config = test_config();
+        config.build_dir = Some("build".to_string());
+
+        assert_eq!(object_path("a/x.s", &config), "build/a/x.s.o");
+        assert_eq!(object_path("./b/x.s", &config), "build/b/x.s.o");
+        assert_eq!(object_path("/opt/src/x.s", &config), "build/opt/src/x.s.o");
+
+        let cwd = std::env::current_dir().unwrap();
+        let absolute = cwd.join("a/x.s");
+        assert_eq!(object_path(absolute.to_str().unwrap(), &config), "build/a/x.s.o");
+
+        config.build_dir = None;
+        assert_eq!(object_path("x.s", &config), "target/rasm/dev/x.s.o");
     }
 }
@@ -0,0 +1,350 @@
+use crate::deps::Dependency;
+use crate::diagnostics::Diagnostic;
+use crate::toolchain::find_in_path;
+use anyhow::{Context, Result};
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Mutex;
+
+/// Directory holding the build manifest and the object cache.
+pub const STATE_DIR: &str = ".rasm";
+
+/// Version of the manifest format; bump when the key layout changes.
+const MANIFEST_VERSION: u32 = 3;
+
+/// How an object or binary was produced during a build.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CacheStatus
+{
+    /// The existing file already matches its key; nothing was done.
+    UpToDate,
+    /// The file was restored from the content-addressed cache.
+    Hit,
+    /// The tool had to be run.
+    Miss,
+}
+
+/// Persistent record of the keys used to produce each output.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct BuildState
+{
+    pub version: u32,
+    /// Object file path -> cache key it was built from.
+    pub objects: BTreeMap<String, String>,
+    /// Output binary path -> key of the link inputs.
+    pub links: BTreeMap<String, String>,
+    /// Object file path -> files its source includes, directly or transitively.
+    pub dependencies: BTreeMap<String, Vec<String>>,
+    /// Cache key -> warnings printed while producing it, replayed on reuse.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub warnings: BTreeMap<String, Vec<Diagnostic>>,
+}
+
+/// Incremental hasher that length-prefixes every field so that
+/// ("ab", "c") and ("a", "bc") produce different keys.
+pub struct KeyHasher(Sha256);
+
+impl KeyHasher
+{
+    pub fn new() -> Self
+    {
+        KeyHasher(Sha256::new())
+    }
+
+    pub fn field(&mut self, bytes: impl AsRef<[u8]>) -> &mut Self
+    {
+        let bytes = bytes.as_ref();
+        self.0.update((bytes.len() as u64).to_le_bytes());
+        self.0.update(bytes);
+        self
+    }
+
+    pub fn finish(self) -> String
+    {
+        self.0
+            .finalize()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect()
+    }
+}
+
+/// Content-addressed object cache backed by a manifest in `.rasm/build-state.json`.
+///
+/// Safe to share between the parallel assembly workers; the manifest is
+/// only written back when `save` is called at the end of a build.
+pub struct BuildCache
+{
+    root: PathBuf,
+    state: Mutex<BuildState>,
+    tool_ids: Mutex<BTreeMap<String, String>>,
+}
+
+impl BuildCache
+{
+    /// Open the cache rooted at `root`, loading the manifest if one exists.
+    /// A missing or unreadable manifest starts an empty build state.
+    pub fn open(root: impl Into<PathBuf>) -> Self
+    {
+        let root = root.into();
+        let manifest = root.join("build-state.json");
+        let state = match fs::read_to_string(&manifest)
+        {
+            Ok(contents) => match serde_json::from_str::<BuildState>(&contents)
+            {
+                Ok(state) if state.version == MANIFEST_VERSION => state,
+                Ok(_) => {
+                    debug!("Discarding build state with old format: {}", manifest.display());
+                    BuildState::default()
+                }
+                Err(e) => {
+                    warn!("Ignoring corrupt build state {}: {}", manifest.display(), e);
+                    BuildState::default()
+                }
+            },
+            Err(_) => BuildState::default(),
+        };
+
+        BuildCache
+        {
+            root,
+            state: Mutex::new(state),
+            tool_ids: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    fn object_store(&self, key: &str) -> PathBuf
+    {
+        self.root.join("cache").join(format!("{}.o", key))
+    }
+
+    /// Identify a tool by its resolved path and `--version` banner, so that
+    /// upgrading the assembler invalidates every key built with it.
+    /// The result is memoized for the lifetime of the cache.
+    pub fn tool_identity(&self, tool: &str) -> String
+    {
+        let mut ids = self.tool_ids.lock().unwrap();
+        if let Some(id) = ids.get(tool)
+        {
+            return id.clone();
+        }
+
+        let version = Command::new(tool)
+            .arg("--version")
+            .output()
+            .map(|out| {
+                let text = if out.stdout.is_empty() { out.stderr } else { out.stdout };
+                String::from_utf8_lossy(&text)
+                    .lines()
+                    .next()
+                    .unwrap_or_default()
+                    .to_string()
+            })
+            .unwrap_or_default();
+        let path = find_in_path(tool).unwrap_or_else(|| PathBuf::from(tool));
+        let id = format!("{}\n{}", path.display(), version);
+        ids.insert(tool.to_string(), id.clone());
+        id
+    }
+
+    /// Check whether `object` exists and was last built from `key`.
+    pub fn is_fresh(&self, object: &str, key: &str) -> bool
+    {
+        let state = self.state.lock().unwrap();
+        state.objects.get(object).map(String::as_str) == Some(key) && Path::new(object).exists()
+    }
+
+    /// Restore `object` from the cache if an entry for `key` exists.
+    pub fn restore(&self, object: &str, key: &str) -> bool
+    {
+        let cached = self.object_store(key);
+        if !cached.exists()
+        {
+            return false;
+        }
+        if let Some(parent) = Path::new(object).parent()
+        {
+            let _ = fs::create_dir_all(parent);
+        }
+        match fs::copy(&cached, object)
+        {
+            Ok(_) => {
+                self.record(object, key);
+                true
+            }
+            Err(e) => {
+                warn!("Failed to restore {} from cache: {}", object, e);
+                false
+            }
+        }
+    }
+
+    /// Store a freshly assembled object in the cache and record its key.
+    pub fn store(&self, object: &str, key: &str)
+    {
+        let cached = self.object_store(key);
+        let stored = cached
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|_| fs::copy(object, &cached));
+        if let Err(e) = stored
+        {
+            warn!("Failed to cache {}: {}", object, e);
+        }
+        self.record(object, key);
+    }
+
+    fn record(&self, object: &str, key: &str)
+    {
+        let mut state = self.state.lock().unwrap();
+        state.objects.insert(object.to_string(), key.to_string());
+    }
+
+    /// Record the files the source of `object` depends on.
+    pub fn record_dependencies(&self, object: &str, deps: &[Dependency])
+    {
+        let found = deps
+            .iter()
+            .filter_map(|dep| match dep
+            {
+                Dependency::Found(path) => Some(path.to_string_lossy().to_string()),
+                Dependency::Missing(_) => None,
+            })
+            .collect();
+        let mut state = self.state.lock().unwrap();
+        state.dependencies.insert(object.to_string(), found);
+    }
+
+    /// All files recorded as dependencies of any object.
+    pub fn dependency_paths(&self) -> Vec<PathBuf>
+    {
+        let state = self.state.lock().unwrap();
+        let paths: std::collections::BTreeSet<&String> = state.dependencies.values().flatten().collect();
+        paths.into_iter().map(PathBuf::from).collect()
+    }
+
+    /// Check whether `output` exists and was last linked from `key`.
+    pub fn is_linked(&self, output: &str, key: &str) -> bool
+    {
+        let state = self.state.lock().unwrap();
+        state.links.get(output).map(String::as_str) == Some(key) && Path::new(output).exists()
+    }
+
+    /// Record the link key of an output binary.
+    pub fn record_link(&self, output: &str, key: &str)
+    {
+        let mut state = self.state.lock().unwrap();
+        state.links.insert(output.to_string(), key.to_string());
+    }
+
+    /// Record the warnings printed while building the output of `key`.
+    pub fn record_warnings(&self, key: &str, warnings: &[Diagnostic])
+    {
+        let mut state = self.state.lock().unwrap();
+        if warnings.is_empty()
+        {
+            state.warnings.remove(key);
+        } else {
+            state.warnings.insert(key.to_string(), warnings.to_vec());
+        }
+    }
+
+    /// Warnings recorded for `key`, so a reused output reports them again.
+    pub fn warnings(&self, key: &str) -> Vec<Diagnostic>
+    {
+        self.state.lock().unwrap().warnings.get(key).cloned().unwrap_or_default()
+    }
+
+    /// Look up the key an object was last built from.
+    pub fn object_key(&self, object: &str) -> Option<String>
+    {
+        self.state.lock().unwrap().objects.get(object).cloned()
+    }
+
+    /// Write the manifest back to disk.
+    pub fn save(&self) -> Result<()>
+    {
+        let mut state = self.state.lock().unwrap();
+        state.version = MANIFEST_VERSION;
+        fs::create_dir_all(&self.root)
+            .with_context(|| format!("Failed to create {}", self.root.display()))?;
+
+        // Write to a temporary file first so an interrupted build never
+        // leaves a truncated manifest behind.
+        let manifest = self.root.join("build-state.json");
+        let tmp = self.root.join("build-state.json.tmp");
+        fs::write(&tmp, serde_json::to_string_pretty(&*state)?)?;
+        fs::rename(&tmp, &manifest)
+            .with_context(|| format!("Failed to write {}", manifest.display()))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("rasm-cache-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_key_fields_are_delimited() {
+        let mut a = KeyHasher::new();
+        a.field("ab").field("c");
+        let mut b = KeyHasher::new();
+        b.field("a").field("bc");
+        assert_ne!(a.finish(), b.finish());
+    }
+
+    #[test]
+    fn test_store_and_restore_object() {
+        let dir = temp_dir("restore");
+        let obj = dir.join("main.s.o");
+        let obj = obj.to_str().unwrap();
+        fs::write(obj, b"object bytes").unwrap();
+
+        let cache = BuildCache::open(dir.join(".rasm"));
+        cache.store(obj, "abc");
+        assert!(cache.is_fresh(obj, "abc"));
+        assert!(!cache.is_fresh(obj, "def"));
+
+        fs::remove_file(obj).unwrap();
+        assert!(!cache.is_fresh(obj, "abc"));
+        assert!(cache.restore(obj, "abc"));
+        assert_eq!(fs::read(obj).unwrap(), b"object bytes");
+        assert!(!cache.restore(obj, "def"));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_manifest_round_trip() {
+        let dir = temp_dir("manifest");
+        let output = dir.join("app");
+        let output = output.to_str().unwrap();
+        fs::write(output, b"binary").unwrap();
+
+        let cache = BuildCache::open(dir.join(".rasm"));
+        cache.record_link(output, "key1");
+        let warning = Diagnostic::new(crate::diagnostics::Severity::Warning, "missing .note.GNU-stack section");
+        cache.record_warnings("key1", std::slice::from_ref(&warning));
+        cache.save().unwrap();
+
+        let reopened = BuildCache::open(dir.join(".rasm"));
+        assert!(reopened.is_linked(output, "key1"));
+        assert!(!reopened.is_linked(output, "key2"));
+        assert_eq!(reopened.warnings("key1"), vec![warning]);
+        assert!(reopened.warnings("key2").is_empty());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
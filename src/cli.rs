@@ -1,173 +1,487 @@
-use clap::Parser;
-use clap_complete;
+use crate::config::FileConfig;
+use crate::events::MessageFormat;
+use crate::report::ReportRequest;
+use clap::{Args, Parser, Subcommand};
+use std::path::PathBuf;
 
-/// Configuration for the RASM assembler and linker.
-/// Can be specified via command-line arguments or a TOML configuration file.
+// Every option is unset unless given, so that it only overrides
+// configuration files when it is actually passed.
+
+/// Command-line interface for the RASM assembler and linker.
 #[derive(Parser, Debug)]
 #[command(author, version, about)]
-pub struct Config 
+pub struct Cli 
 {
-    /// Input assembly file(s) (supports glob patterns)
-    #[arg(required_unless_present = "completions")]
-    pub input_files: Vec<String>,
+    #[command(subcommand)]
+    pub command: Option<Command>,
 
-    /// Output binary file
-    #[arg(short = 'o', required_unless_present = "completions", default_value = "")]
-    pub output_file: String,
+    #[command(flatten)]
+    pub options: GlobalOptions,
 
-    /// Extra flags for the linker (passed as-is)
-    #[arg(last = true)]
-    pub extra_flags: Vec<String>,
+    /// Print information and exit
+    #[arg(long, value_name = "WHAT")]
+    pub print: Option<PrintRequest>,
+}
 
+/// Settings accepted by every subcommand.
+#[derive(Args, Debug, Clone, Default)]
+pub struct GlobalOptions 
+{
     /// Assembler to use (default: "as")
-    #[arg(long, default_value = "as")]
-    pub assembler: String,
+    #[arg(long, global = true)]
+    pub assembler: Option<String>,
 
     /// Additional flags to pass to the assembler
-    #[arg(long)]
+    #[arg(long, global = true)]
     pub assembler_flags: Vec<String>,
 
-    /// Target architecture (default: "arm64" on macOS, "x86_64" otherwise)
-    #[arg(long, default_value_t = default_target())]
-    pub target: String,
+    /// Linker to use: ld, ld.lld, ld64.lld, mold, gold, or a cc/clang driver
+    /// (default: the target's linker)
+    #[arg(long, global = true)]
+    pub linker: Option<String>,
+
+    /// Archiver for static libraries (default: "ar", or the target's cross ar)
+    #[arg(long, global = true)]
+    pub archiver: Option<String>,
+
+    /// Target triple or architecture, e.g. "aarch64-apple-darwin" or "x86_64" (default: host)
+    #[arg(long, global = true)]
+    pub target: Option<String>,
 
     /// Enable verbose logging
-    #[arg(short, long, action = clap::ArgAction::SetTrue)]
+    #[arg(short, long, overrides_with = "no_verbose", global = true)]
     pub verbose: bool,
 
+    /// Disable verbose logging, overriding the configuration file
+    #[arg(long, overrides_with = "verbose", global = true)]
+    pub no_verbose: bool,
+
     /// Dry-run: print commands without executing
-    #[arg(long, action = clap::ArgAction::SetTrue)]
+    #[arg(long, overrides_with = "no_dry_run", global = true)]
     pub dry_run: bool,
 
-    /// Clean generated files (object and binary)
-    #[arg(short, long, action = clap::ArgAction::SetTrue)]
-    pub clean: bool,
+    /// Execute commands, overriding `dry_run` in the configuration file
+    #[arg(long, overrides_with = "dry_run", global = true)]
+    pub no_dry_run: bool,
+
+    /// Fail the build when the assembler or linker prints warnings
+    #[arg(long, overrides_with = "no_deny_warnings", global = true)]
+    pub deny_warnings: bool,
+
+    /// Allow warnings, overriding `deny_warnings` in the configuration file
+    #[arg(long, overrides_with = "deny_warnings", global = true)]
+    pub no_deny_warnings: bool,
+
+    /// Directory for object files, mirroring the source tree (default: "target/rasm/<profile>")
+    #[arg(long, value_name = "DIR", global = true)]
+    pub build_dir: Option<String>,
+
+    /// Build profile to use (built-in: "dev", "release")
+    #[arg(long, value_name = "NAME", default_value = "dev", global = true)]
+    pub profile: String,
+
+    /// Build with the release profile (same as --profile release)
+    #[arg(long, action = clap::ArgAction::SetTrue, conflicts_with = "profile", global = true)]
+    pub release: bool,
 
     /// Path to configuration file (TOML format)
-    #[arg(long)]
+    #[arg(long, global = true)]
     pub config_file: Option<String>,
 
-    /// Watch for file changes and rebuild automatically
-    #[arg(short, long, action = clap::ArgAction::SetTrue)]
-    pub watch: bool,
-
     /// Enable colored output (auto-detected by default)
-    #[arg(long, value_name = "WHEN", default_value = "auto")]
-    pub color: String,
+    #[arg(long, value_name = "WHEN", global = true)]
+    pub color: Option<String>,
+
+    /// Number of files to assemble in parallel (default: one per CPU)
+    #[arg(short, long, value_name = "N", global = true)]
+    pub jobs: Option<usize>,
+
+    /// How to report build progress: "human" text or "json" events, one per line
+    #[arg(long, value_name = "FORMAT", value_enum, global = true)]
+    pub message_format: Option<MessageFormat>,
+
+    /// Write a CI report after building: github, gitlab, junit or sarif,
+    /// to PATH or the format's default (can be repeated)
+    #[arg(long = "report", value_name = "FORMAT[=PATH]", global = true)]
+    pub reports: Vec<ReportRequest>,
+
+    /// Write the build summary as JSON to PATH; a workspace root passes this
+    /// to its members to combine their reports
+    #[arg(long, value_name = "PATH", global = true, hide = true)]
+    pub summary_file: Option<PathBuf>,
+}
+
+/// Subcommands of `rasm`.
+#[derive(Subcommand, Debug, Clone, PartialEq, Eq)]
+pub enum Command 
+{
+    /// Assemble and link the project
+    Build 
+    {
+        #[command(flatten)]
+        args: BuildArgs,
+        #[command(flatten)]
+        members: MemberArgs,
+    },
+    /// Remove generated object and binary files
+    Clean 
+    {
+        #[command(flatten)]
+        inputs: Inputs,
+        #[command(flatten)]
+        members: MemberArgs,
+    },
+    /// Rebuild whenever a source or included file changes
+    Watch(BuildArgs),
+    /// Build the project, then run the binary
+    Run(RunArgs),
+    /// Assemble every source to check for errors, without linking
+    Check 
+    {
+        #[command(flatten)]
+        inputs: Inputs,
+        #[command(flatten)]
+        members: MemberArgs,
+        /// Check every file and report all errors, instead of stopping at the first
+        #[arg(short = 'k', long)]
+        keep_going: bool,
+    },
+    /// Create a new project with a .rasm.toml and a starter source file
+    Init(InitArgs),
+    /// Generate shell completions
+    Completions 
+    {
+        /// Shell to generate completions for
+        shell: clap_complete::Shell,
+    },
+    /// Inspect the effective configuration
+    #[command(subcommand)]
+    Config(ConfigCommand),
+}
+
+/// Sources and output, falling back to `input_files` and `output_file`
+/// from the configuration file when not given.
+#[derive(Args, Debug, Clone, Default, PartialEq, Eq)]
+pub struct Inputs 
+{
+    /// Input assembly file(s) (supports glob patterns)
+    pub input_files: Vec<String>,
 
-    /// Generate shell completions for the specified shell
-    #[arg(long, value_name = "SHELL")]
-    pub completions: Option<clap_complete::Shell>,
+    /// Output binary file
+    #[arg(short = 'o')]
+    pub output_file: Option<String>,
 }
 
-/// Returns the default target architecture based on the current platform.
-/// Returns "arm64" on macOS, "x86_64" on other platforms.
-fn default_target() -> String 
+/// Selection of workspace members for `build`, `check` and `clean`.
+#[derive(Args, Debug, Clone, Default, PartialEq, Eq)]
+pub struct MemberArgs 
 {
-    if cfg!(target_os = "macos") 
+    /// Run for every member of the workspace
+    #[arg(long, conflicts_with_all = ["input_files", "output_file"])]
+    pub workspace: bool,
+
+    /// Run for the named workspace member (can be repeated)
+    #[arg(short = 'p', long = "package", value_name = "NAME", conflicts_with_all = ["input_files", "output_file"])]
+    pub packages: Vec<String>,
+}
+
+impl MemberArgs 
+{
+    /// Whether any workspace members were asked for.
+    pub fn is_set(&self) -> bool 
     {
-        "arm64".to_string()
-    } else {
-        "x86_64".to_string()
+        self.workspace || !self.packages.is_empty()
     }
 }
 
-/// Parse command-line arguments and return a Config struct.
-pub fn parse_args() -> Config 
+/// Arguments of `rasm build` and `rasm watch`.
+#[derive(Args, Debug, Clone, Default, PartialEq, Eq)]
+pub struct BuildArgs 
+{
+    #[command(flatten)]
+    pub inputs: Inputs,
+
+    /// Rebuild all files, even if they are up to date
+    #[arg(long)]
+    pub force: bool,
+
+    /// Assemble every file and report all errors, instead of stopping at the first
+    #[arg(short = 'k', long)]
+    pub keep_going: bool,
+
+    /// Extra flags for the linker (passed as-is)
+    #[arg(last = true)]
+    pub extra_flags: Vec<String>,
+}
+
+/// Arguments of `rasm run`.
+#[derive(Args, Debug, Clone, Default, PartialEq, Eq)]
+pub struct RunArgs 
 {
-    Config::parse()
+    #[command(flatten)]
+    pub inputs: Inputs,
+
+    /// Rebuild all files, even if they are up to date
+    #[arg(long)]
+    pub force: bool,
+
+    /// Executable to run, by its [[bin]] name (needed when there are several)
+    #[arg(long, value_name = "NAME")]
+    pub bin: Option<String>,
+
+    /// Arguments passed to the program
+    #[arg(last = true)]
+    pub args: Vec<String>,
 }
 
-impl Config {
-    /// Merge another configuration into self. Values from `other` override self if set.
-    pub fn merge(&mut self, other: Config) 
+/// Arguments of `rasm init`.
+#[derive(Args, Debug, Clone, PartialEq, Eq)]
+pub struct InitArgs 
+{
+    /// Project directory (created if missing)
+    #[arg(default_value = ".")]
+    pub path: PathBuf,
+
+    /// Name of the output binary (default: the directory name)
+    #[arg(long)]
+    pub name: Option<String>,
+}
+
+/// Subcommands of `rasm config`.
+#[derive(Subcommand, Debug, Clone, PartialEq, Eq)]
+pub enum ConfigCommand 
+{
+    /// Print every effective configuration value
+    Show 
     {
-        if !other.input_files.is_empty() 
-        {
-            self.input_files = other.input_files;
-        }
-        if !other.output_file.is_empty() 
-        {
-            self.output_file = other.output_file;
-        }
-        if !other.extra_flags.is_empty() 
+        /// Show where each value came from
+        #[arg(long)]
+        origin: bool,
+    },
+    /// Check the configuration files for errors without building
+    Check,
+    /// Print a JSON Schema for .rasm.toml, for editor completion
+    Schema,
+}
+
+/// Information that can be printed with `--print`.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PrintRequest 
+{
+    /// The built-in target database
+    TargetList,
+}
+
+/// Parse command-line arguments.
+pub fn parse_args() -> Cli 
+{
+    Cli::parse()
+}
+
+/// Turn a `--flag`/`--no-flag` pair into an optional setting.
+fn flag(on: bool, off: bool) -> Option<bool> 
+{
+    match (on, off) 
+    {
+        (true, _) => Some(true),
+        (_, true) => Some(false),
+        _ => None,
+    }
+}
+
+/// Turn a repeatable list option into an optional setting.
+fn list(values: &[String]) -> Option<Vec<String>> 
+{
+    (!values.is_empty()).then(|| values.to_vec())
+}
+
+impl Command 
+{
+    /// Sources and output given to the subcommand, if it takes any.
+    fn inputs(&self) -> Option<&Inputs> 
+    {
+        match self 
         {
-            self.extra_flags = other.extra_flags;
+            Command::Build { args, .. } | Command::Watch(args) => Some(&args.inputs),
+            Command::Run(args) => Some(&args.inputs),
+            Command::Clean { inputs, .. } | Command::Check { inputs, .. } => Some(inputs),
+            _ => None,
         }
-        if other.assembler != "as" 
+    }
+
+    /// Workspace members the subcommand was asked to run for, if it supports them.
+    pub fn members(&self) -> Option<&MemberArgs> 
+    {
+        match self 
         {
-            self.assembler = other.assembler;
+            Command::Build { members, .. } | Command::Clean { members, .. } | Command::Check { members, .. } => {
+                Some(members)
+            }
+            _ => None,
         }
-        if !other.assembler_flags.is_empty() 
+    }
+
+    /// Arguments that run this subcommand in a workspace member: its name,
+    /// `--force`, `--keep-going` and any extra linker flags. Sources and `-o`
+    /// come from the member's own configuration file.
+    pub fn member_args(&self) -> Vec<String> 
+    {
+        match self 
         {
-            self.assembler_flags = other.assembler_flags;
+            Command::Build { args, .. } => {
+                let mut out = vec!["build".to_string()];
+                if args.force 
+                {
+                    out.push("--force".to_string());
+                }
+                if args.keep_going 
+                {
+                    out.push("--keep-going".to_string());
+                }
+                if !args.extra_flags.is_empty() 
+                {
+                    out.push("--".to_string());
+                    out.extend(args.extra_flags.iter().cloned());
+                }
+                out
+            }
+            Command::Clean { .. } => vec!["clean".to_string()],
+            Command::Check { keep_going, .. } => {
+                let mut out = vec!["check".to_string()];
+                if *keep_going 
+                {
+                    out.push("--keep-going".to_string());
+                }
+                out
+            }
+            _ => Vec::new(),
         }
-        if other.target != default_target() 
+    }
+
+    /// Whether `--force` was given.
+    pub fn force(&self) -> bool 
+    {
+        match self 
         {
-            self.target = other.target;
+            Command::Build { args, .. } | Command::Watch(args) => args.force,
+            Command::Run(args) => args.force,
+            _ => false,
         }
-        if other.verbose 
+    }
+
+    /// Whether `--keep-going` was given.
+    pub fn keep_going(&self) -> bool 
+    {
+        match self 
         {
-            self.verbose = true;
+            Command::Build { args, .. } | Command::Watch(args) => args.keep_going,
+            Command::Check { keep_going, .. } => *keep_going,
+            _ => false,
         }
-        if other.dry_run 
+    }
+}
+
+impl GlobalOptions 
+{
+    /// These options as command-line arguments, for running rasm in
+    /// workspace members. The configuration file and color are left out:
+    /// each member finds its own file, and the caller decides on color.
+    /// Reports are left out too, as the caller combines the members' into one.
+    pub fn to_args(&self) -> Vec<String> 
+    {
+        let mut args = Vec::new();
+        let mut push = |name: &str, value: &Option<String>| {
+            if let Some(value) = value 
+            {
+                args.push(format!("--{}={}", name, value));
+            }
+        };
+        push("assembler", &self.assembler);
+        push("linker", &self.linker);
+        push("archiver", &self.archiver);
+        push("target", &self.target);
+        push("build-dir", &self.build_dir);
+        push("jobs", &self.jobs.map(|jobs| jobs.to_string()));
+        if self.message_format == Some(MessageFormat::Json) 
         {
-            self.dry_run = true;
+            args.push("--message-format=json".to_string());
         }
-        if other.clean 
+        for flag in &self.assembler_flags 
         {
-            self.clean = true;
+            args.push(format!("--assembler-flags={}", flag));
         }
-        if other.watch 
+        let switches = [
+            (self.verbose, self.no_verbose, "verbose"),
+            (self.dry_run, self.no_dry_run, "dry-run"),
+            (self.deny_warnings, self.no_deny_warnings, "deny-warnings"),
+        ];
+        for (on, off, name) in switches 
         {
-            self.watch = true;
+            match flag(on, off) 
+            {
+                Some(true) => args.push(format!("--{}", name)),
+                Some(false) => args.push(format!("--no-{}", name)),
+                None => {}
+            }
         }
-        if other.color != "auto" 
+        if self.release 
         {
-            self.color = other.color;
+            args.push("--release".to_string());
+        } else {
+            args.push(format!("--profile={}", self.profile));
         }
+        args
     }
+}
 
-    /// Validate the configuration and return an error if invalid.
-    pub fn validate(&self) -> anyhow::Result<()> 
+impl Cli 
+{
+    /// The configuration layer formed by the options that were passed.
+    pub fn layer(&self) -> FileConfig 
     {
-        if self.input_files.is_empty() 
+        let options = &self.options;
+        let inputs = self.command.as_ref().and_then(Command::inputs);
+        let extra_flags = match self.command 
         {
-            return Err(anyhow::anyhow!("No input files specified"));
-        }
-        
-        if self.output_file.is_empty() 
-        {
-            return Err(anyhow::anyhow!("Output file must be specified"));
-        }
-        
-        if self.assembler.is_empty() 
-        {
-            return Err(anyhow::anyhow!("Assembler command cannot be empty"));
-        }
-        
-        // Validate that input files don't have suspicious paths
-        for input in &self.input_files 
+            Some(Command::Build { ref args, .. }) | Some(Command::Watch(ref args)) => list(&args.extra_flags),
+            _ => None,
+        };
+
+        FileConfig 
         {
-            if input.contains("..") 
-            {
-                return Err(anyhow::anyhow!(
-                    "Input file paths cannot contain '..': {}", 
-                    input
-                ));
-            }
+            extends: None,
+            include: None,
+            input_files: inputs.and_then(|i| list(&i.input_files)),
+            output_file: inputs.and_then(|i| i.output_file.clone()),
+            extra_flags,
+            assembler: options.assembler.clone(),
+            assembler_flags: list(&options.assembler_flags),
+            linker: options.linker.clone(),
+            archiver: options.archiver.clone(),
+            target: options.target.clone(),
+            build_dir: options.build_dir.clone(),
+            verbose: flag(options.verbose, options.no_verbose),
+            dry_run: flag(options.dry_run, options.no_dry_run),
+            deny_warnings: flag(options.deny_warnings, options.no_deny_warnings),
+            color: options.color.clone(),
+            jobs: options.jobs,
+            profile: None,
+            workspace: None,
+            bin: None,
+            staticlib: None,
+            sharedlib: None,
         }
-        
-        // Validate output path
-        if self.output_file.contains("..") 
+    }
+
+    /// Name of the selected build profile.
+    pub fn profile(&self) -> &str 
+    {
+        if self.options.release 
         {
-            return Err(anyhow::anyhow!(
-                "Output file path cannot contain '..': {}", 
-                self.output_file
-            ));
+            "release"
+        } else {
+            &self.options.profile
         }
-        
-        Ok(())
     }
 }
 
@@ -175,79 +489,115 @@ impl Config {
 mod tests {
     use super::*;
 
-    fn create_test_config() -> Config {
-        Config {
-            input_files: vec!["test.s".to_string()],
-            output_file: "test.out".to_string(),
-            extra_flags: vec![],
-            assembler: "as".to_string(),
-            assembler_flags: vec![],
-            target: "x86_64".to_string(),
-            verbose: false,
-            dry_run: false,
-            clean: false,
-            config_file: None,
-            watch: false,
-            color: "auto".to_string(),
-            completions: None,
-        }
+    fn parse(args: &[&str]) -> Cli {
+        Cli::try_parse_from(std::iter::once("rasm").chain(args.iter().copied())).unwrap()
+    }
+
+    #[test]
+    fn test_unset_options_stay_unset() {
+        let layer = parse(&["build", "-o", "app", "main.s"]).layer();
+        assert_eq!(layer.input_files, Some(vec!["main.s".to_string()]));
+        assert_eq!(layer.output_file.as_deref(), Some("app"));
+        assert_eq!(layer.assembler, None);
+        assert_eq!(layer.target, None);
+        assert_eq!(layer.verbose, None);
+        assert_eq!(layer.color, None);
+    }
+
+    #[test]
+    fn test_default_values_are_explicit() {
+        let layer = parse(&["build", "-o", "app", "--assembler", "as", "--color", "auto", "main.s"]).layer();
+        assert_eq!(layer.assembler.as_deref(), Some("as"));
+        assert_eq!(layer.color.as_deref(), Some("auto"));
+    }
+
+    #[test]
+    fn test_negated_flags() {
+        assert_eq!(parse(&["build", "-v"]).layer().verbose, Some(true));
+        assert_eq!(parse(&["build", "--no-verbose"]).layer().verbose, Some(false));
+        assert_eq!(parse(&["build", "-v", "--no-verbose"]).layer().verbose, Some(false));
+        assert_eq!(parse(&["build", "--no-dry-run"]).layer().dry_run, Some(false));
+        assert_eq!(parse(&["check", "--deny-warnings"]).layer().deny_warnings, Some(true));
+        assert_eq!(parse(&["build", "--deny-warnings", "--no-deny-warnings"]).layer().deny_warnings, Some(false));
     }
 
     #[test]
-    fn test_validate_valid_config() {
-        let config = create_test_config();
-        assert!(config.validate().is_ok());
+    fn test_release_selects_profile() {
+        assert_eq!(parse(&["build"]).profile(), "dev");
+        assert_eq!(parse(&["build", "--release"]).profile(), "release");
+        assert_eq!(parse(&["--profile", "bench", "build"]).profile(), "bench");
     }
 
     #[test]
-    fn test_validate_empty_input_files() {
-        let mut config = create_test_config();
-        config.input_files = vec![];
-        assert!(config.validate().is_err());
+    fn test_build_needs_no_inputs() {
+        let cli = parse(&["build"]);
+        assert_eq!(
+            cli.command,
+            Some(Command::Build { args: BuildArgs::default(), members: MemberArgs::default() })
+        );
+        assert_eq!(cli.layer().input_files, None);
+        assert_eq!(cli.layer().output_file, None);
     }
 
     #[test]
-    fn test_validate_empty_output_file() {
-        let mut config = create_test_config();
-        config.output_file = String::new();
-        assert!(config.validate().is_err());
+    fn test_trailing_args() {
+        let build = parse(&["build", "-o", "app", "main.s", "--", "-lc"]).layer();
+        assert_eq!(build.extra_flags, Some(vec!["-lc".to_string()]));
+
+        let cli = parse(&["run", "--", "--port", "80"]);
+        assert_eq!(cli.layer().extra_flags, None);
+        match cli.command {
+            Some(Command::Run(args)) => assert_eq!(args.args, vec!["--port", "80"]),
+            other => panic!("unexpected command: {:?}", other),
+        }
     }
 
     #[test]
-    fn test_validate_path_traversal_input() {
-        let mut config = create_test_config();
-        config.input_files = vec!["../evil.s".to_string()];
-        assert!(config.validate().is_err());
+    fn test_force_and_clean_inputs() {
+        assert!(parse(&["watch", "--force"]).command.unwrap().force());
+        let clean = parse(&["clean", "-o", "app", "a.s", "b.s"]).layer();
+        assert_eq!(clean.input_files, Some(vec!["a.s".to_string(), "b.s".to_string()]));
+        assert_eq!(clean.output_file.as_deref(), Some("app"));
     }
 
     #[test]
-    fn test_validate_path_traversal_output() {
-        let mut config = create_test_config();
-        config.output_file = "../evil.out".to_string();
-        assert!(config.validate().is_err());
+    fn test_workspace_selection() {
+        let cli = parse(&["build", "--workspace", "--force", "--", "-lc"]);
+        let command = cli.command.unwrap();
+        assert!(command.members().unwrap().workspace);
+        assert_eq!(command.member_args(), vec!["build", "--force", "--", "-lc"]);
+        let command = parse(&["check", "--workspace", "-k"]).command.unwrap();
+        assert!(command.keep_going());
+        assert_eq!(command.member_args(), vec!["check", "--keep-going"]);
+
+        let cli = parse(&["check", "-p", "hello", "--package", "fibonacci"]);
+        assert_eq!(cli.command.unwrap().members().unwrap().packages, vec!["hello", "fibonacci"]);
+
+        let err = Cli::try_parse_from(["rasm", "build", "--workspace", "main.s"]).unwrap_err();
+        assert_eq!(err.kind(), clap::error::ErrorKind::ArgumentConflict);
     }
 
     #[test]
-    fn test_merge_configs() {
-        let mut config1 = create_test_config();
-        let mut config2 = create_test_config();
-        
-        config2.input_files = vec!["new.s".to_string()];
-        config2.verbose = true;
-        
-        config1.merge(config2);
-        
-        assert_eq!(config1.input_files, vec!["new.s".to_string()]);
-        assert!(config1.verbose);
+    fn test_global_options_round_trip() {
+        let cli = parse(&[
+            "--assembler", "nasm", "--assembler-flags=-g", "--no-verbose", "-j", "2", "--release", "--message-format", "json",
+            "--report", "junit=out/junit.xml", "build",
+        ]);
+        let args = cli.options.to_args();
+        assert_eq!(
+            args,
+            vec!["--assembler=nasm", "--jobs=2", "--message-format=json", "--assembler-flags=-g", "--no-verbose", "--release"]
+        );
+        let again = Cli::try_parse_from(std::iter::once("rasm".to_string()).chain(args).chain(["build".to_string()])).unwrap();
+        assert_eq!(again.layer(), cli.layer());
+        assert_eq!(again.profile(), "release");
     }
 
     #[test]
-    fn test_default_target() {
-        let target = default_target();
-        #[cfg(target_os = "macos")]
-        assert_eq!(target, "arm64");
-        
-        #[cfg(not(target_os = "macos"))]
-        assert_eq!(target, "x86_64");
+    fn test_config_show_accepts_settings() {
+        let cli = parse(&["--assembler", "nasm", "config", "show", "--origin", "--no-verbose"]);
+        assert_eq!(cli.command, Some(Command::Config(ConfigCommand::Show { origin: true })));
+        assert_eq!(cli.layer().assembler.as_deref(), Some("nasm"));
+        assert_eq!(cli.layer().verbose, Some(false));
     }
 }
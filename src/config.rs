@@ -1,91 +1,1595 @@
-use crate::cli::Config;
-use anyhow::Result;
+use anyhow::{Context, Result};
+use std::collections::BTreeMap;
+use std::fmt;
 use std::fs;
+use std::path::{Path, PathBuf};
 
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
-/// Configuration structure for loading from TOML files.
-/// All fields are optional to allow partial configuration.
-#[derive(Deserialize, Debug)]
+use crate::events::MessageFormat;
+use crate::report::ReportRequest;
+use crate::target::ObjectFormat;
+
+/// One layer of configuration, as loaded from a TOML file or built from
+/// the command line. All fields are optional; unset fields fall through
+/// to lower layers.
+#[derive(Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(deny_unknown_fields)]
 pub struct FileConfig 
 {
+    /// Base configuration file this one builds on, relative to this file.
+    pub extends: Option<String>,
+    /// Configuration fragments merged in order after `extends`, relative to this file.
+    pub include: Option<Vec<String>>,
     pub input_files: Option<Vec<String>>,
     pub output_file: Option<String>,
     pub extra_flags: Option<Vec<String>>,
     pub assembler: Option<String>,
     pub assembler_flags: Option<Vec<String>>,
+    pub linker: Option<String>,
+    /// Archiver for static libraries.
+    pub archiver: Option<String>,
     pub target: Option<String>,
+    pub build_dir: Option<String>,
     pub verbose: Option<bool>,
     pub dry_run: Option<bool>,
-    pub clean: Option<bool>,
-    pub watch: Option<bool>,
+    /// Fail the build when the assembler or linker prints warnings.
+    pub deny_warnings: Option<bool>,
     pub color: Option<String>,
+    /// Number of files to assemble in parallel.
+    pub jobs: Option<usize>,
+    /// Build profiles, as `[profile.<name>]` tables.
+    pub profile: Option<BTreeMap<String, Profile>>,
+    /// Projects built together by `rasm build --workspace`.
+    pub workspace: Option<Workspace>,
+    /// Executables, as `[[bin]]` tables.
+    pub bin: Option<Vec<ArtifactTable>>,
+    /// Static libraries, as `[[staticlib]]` tables.
+    pub staticlib: Option<Vec<ArtifactTable>>,
+    /// Shared libraries, as `[[sharedlib]]` tables.
+    pub sharedlib: Option<Vec<ArtifactTable>>,
+}
+
+/// A `[[bin]]`, `[[staticlib]]` or `[[sharedlib]]` table: one artifact
+/// built from its own sources.
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct ArtifactTable 
+{
+    pub name: String,
+    /// Sources of this artifact (supports glob patterns).
+    pub input_files: Vec<String>,
+    /// Output path; defaults to the name, or "lib<name>.a" and
+    /// "lib<name>.so" (".dylib", ".dll") for libraries.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output_file: Option<String>,
+    /// Entry point symbol of an executable; defaults to the target's.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub entry: Option<String>,
+    /// Linker flags appended to the top-level `extra_flags`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub extra_flags: Vec<String>,
+    /// Name a shared library is recorded under by programs linked against
+    /// it; defaults to its file name ("@rpath/<file name>" on macOS).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub soname: Option<String>,
+    /// Symbols a shared library exports; all others are hidden.
+    /// Defaults to every global symbol.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exported_symbols: Option<Vec<String>>,
+}
+
+/// The `[workspace]` table: projects that are built together.
+#[derive(Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Workspace 
+{
+    /// Member project directories, relative to this file (supports glob patterns).
+    pub members: Vec<String>,
+    /// Directories matched by `members` that are not members.
+    #[serde(default)]
+    pub exclude: Vec<String>,
+}
+
+/// Settings of a build profile (`[profile.dev]`, `[profile.release]`, ...).
+/// Unset fields are inherited from the parent profile.
+#[derive(Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Profile 
+{
+    /// Profile to inherit unset fields from (default: "dev" for custom profiles).
+    pub inherits: Option<String>,
+    pub assembler_flags: Option<Vec<String>>,
+    pub extra_flags: Option<Vec<String>>,
+    /// Emit debug information when assembling.
+    pub debug: Option<bool>,
+    /// Strip symbols from the linked output.
+    pub strip: Option<bool>,
+    pub build_dir: Option<String>,
+}
+
+impl FileConfig 
+{
+    /// Make the relative paths in this layer relative to `base`, for options
+    /// given in a subdirectory of the project (see `project_base`).
+    pub fn rebase(&mut self, base: &Path) 
+    {
+        let rebase = |path: &String| {
+            if Path::new(path).is_absolute() 
+            {
+                path.clone()
+            } else {
+                base.join(path).to_string_lossy().into_owned()
+            }
+        };
+
+        if let Some(ref mut inputs) = self.input_files 
+        {
+            *inputs = inputs.iter().map(rebase).collect();
+        }
+        self.output_file = self.output_file.as_ref().map(rebase);
+        self.build_dir = self.build_dir.as_ref().map(rebase);
+    }
+
+    /// Merge this file on top of `base`, the result of its `extends` and
+    /// `include` files.
+    ///
+    /// Flag lists (`assembler_flags`, `extra_flags`) are appended to the
+    /// base's, so shared flags accumulate. Every other key, including
+    /// `input_files`, replaces the base value. Profiles are merged by name
+    /// with the same rules.
+    fn merge_onto(self, base: FileConfig) -> FileConfig 
+    {
+        let mut profiles = base.profile.unwrap_or_default();
+        for (name, profile) in self.profile.unwrap_or_default() 
+        {
+            let merged = match profiles.remove(&name) 
+            {
+                Some(base) => profile.merge_onto(base),
+                None => profile,
+            };
+            profiles.insert(name, merged);
+        }
+
+        FileConfig 
+        {
+            extends: None,
+            include: None,
+            input_files: self.input_files.or(base.input_files),
+            output_file: self.output_file.or(base.output_file),
+            extra_flags: append(base.extra_flags, self.extra_flags),
+            assembler: self.assembler.or(base.assembler),
+            assembler_flags: append(base.assembler_flags, self.assembler_flags),
+            linker: self.linker.or(base.linker),
+            archiver: self.archiver.or(base.archiver),
+            target: self.target.or(base.target),
+            build_dir: self.build_dir.or(base.build_dir),
+            verbose: self.verbose.or(base.verbose),
+            dry_run: self.dry_run.or(base.dry_run),
+            deny_warnings: self.deny_warnings.or(base.deny_warnings),
+            color: self.color.or(base.color),
+            jobs: self.jobs.or(base.jobs),
+            profile: (!profiles.is_empty()).then_some(profiles),
+            // A workspace describes the file's own directory, so it is not inherited.
+            workspace: self.workspace,
+            bin: self.bin.or(base.bin),
+            staticlib: self.staticlib.or(base.staticlib),
+            sharedlib: self.sharedlib.or(base.sharedlib),
+        }
+    }
+}
+
+/// Concatenate two optional lists, keeping None only if both are unset.
+fn append(base: Option<Vec<String>>, extra: Option<Vec<String>>) -> Option<Vec<String>> 
+{
+    match (base, extra) 
+    {
+        (Some(mut base), Some(extra)) => {
+            base.extend(extra);
+            Some(base)
+        }
+        (base, extra) => base.or(extra),
+    }
+}
+
+impl Profile 
+{
+    /// Merge this profile on top of the same profile from a base file,
+    /// with the rules of `FileConfig::merge_onto`.
+    fn merge_onto(self, base: Profile) -> Profile 
+    {
+        Profile 
+        {
+            inherits: self.inherits.or(base.inherits),
+            assembler_flags: append(base.assembler_flags, self.assembler_flags),
+            extra_flags: append(base.extra_flags, self.extra_flags),
+            debug: self.debug.or(base.debug),
+            strip: self.strip.or(base.strip),
+            build_dir: self.build_dir.or(base.build_dir),
+        }
+    }
+
+    /// Fill fields that are unset in self from `parent`.
+    fn inherit(mut self, parent: Profile) -> Profile 
+    {
+        self.assembler_flags = self.assembler_flags.or(parent.assembler_flags);
+        self.extra_flags = self.extra_flags.or(parent.extra_flags);
+        self.debug = self.debug.or(parent.debug);
+        self.strip = self.strip.or(parent.strip);
+        self.build_dir = self.build_dir.or(parent.build_dir);
+        self
+    }
 }
 
-impl From<FileConfig> for Config 
+/// Built-in defaults for the "dev" and "release" profiles.
+fn builtin_profile(name: &str) -> Option<Profile> 
 {
-    fn from(value: FileConfig) -> Self 
+    match name 
     {
-        Config 
+        "dev" => Some(Profile { debug: Some(true), strip: Some(false), ..Profile::default() }),
+        "release" => Some(Profile { debug: Some(false), strip: Some(true), ..Profile::default() }),
+        _ => None,
+    }
+}
+
+/// Resolve a profile by name, following `inherits` chains and falling back
+/// to the built-in "dev" and "release" defaults.
+/// 
+/// # Returns
+/// The fully inherited profile, or an error for unknown profiles and cycles
+pub fn resolve_profile(name: &str, profiles: &BTreeMap<String, Profile>) -> Result<Profile> 
+{
+    let mut chain: Vec<String> = Vec::new();
+    let mut resolved = Profile::default();
+    let mut current = Some(name.to_string());
+
+    while let Some(name) = current 
+    {
+        if chain.contains(&name) 
+        {
+            chain.push(name);
+            return Err(anyhow::anyhow!("Profile inheritance cycle: {}", chain.join(" -> ")));
+        }
+
+        let builtin = builtin_profile(&name);
+        let profile = match (profiles.get(&name), builtin) 
         {
-            input_files: value.input_files.unwrap_or_default(),
-            output_file: value.output_file.unwrap_or_default(),
-            extra_flags: value.extra_flags.unwrap_or_default(),
-            assembler: value.assembler.unwrap_or_else(|| "as".to_string()),
-            assembler_flags: value.assembler_flags.unwrap_or_default(),
-            target: value.target.unwrap_or_else(|| {
-                if cfg!(target_os = "macos") 
+            (Some(user), Some(builtin)) => user.clone().inherit(builtin),
+            (Some(user), None) => user.clone(),
+            (None, Some(builtin)) => builtin,
+            (None, None) => {
+                let known = profiles.keys().map(String::as_str).chain(["dev", "release"]);
+                return Err(match crate::schema::suggest(&name, known) 
                 {
-                    "arm64".to_string()
-                } else {
-                    "x86_64".to_string()
+                    Some(candidate) => anyhow::anyhow!("Unknown profile: {}\nhelp: did you mean `{}`?", name, candidate),
+                    None => anyhow::anyhow!("Unknown profile: {}", name),
+                });
+            }
+        };
+
+        current = match (&profile.inherits, name.as_str()) 
+        {
+            (Some(parent), _) => Some(parent.clone()),
+            (None, "dev" | "release") => None,
+            (None, _) => Some("dev".to_string()),
+        };
+        chain.push(name);
+        resolved = resolved.inherit(profile);
+    }
+
+    Ok(resolved)
+}
+
+/// Returns the default target: the triple of the host RASM runs on.
+pub fn default_target() -> String 
+{
+    crate::target::host().triple.to_string()
+}
+
+/// Where a configuration value came from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Source 
+{
+    /// Built-in default.
+    Default,
+    /// A configuration file.
+    File(String),
+    /// An environment variable.
+    Env(&'static str),
+    /// A command-line option.
+    Cli,
+    /// The selected build profile, `[profile.<name>]`.
+    Profile(String),
+}
+
+impl fmt::Display for Source 
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result 
+    {
+        match self 
+        {
+            Source::Default => write!(f, "default"),
+            Source::File(path) => write!(f, "config file {}", path),
+            Source::Env(var) => write!(f, "environment variable {}", var),
+            Source::Cli => write!(f, "command line"),
+            Source::Profile(name) => write!(f, "profile.{}", name),
+        }
+    }
+}
+
+/// A configuration layer together with its origin.
+#[derive(Debug, Clone)]
+pub struct Layer 
+{
+    pub source: Source,
+    pub values: FileConfig,
+}
+
+impl Layer 
+{
+    /// The built-in defaults, which form the lowest layer.
+    pub fn defaults() -> Layer 
+    {
+        Layer 
+        {
+            source: Source::Default,
+            values: FileConfig 
+            {
+                extends: None,
+                include: None,
+                input_files: Some(Vec::new()),
+                output_file: Some(String::new()),
+                extra_flags: Some(Vec::new()),
+                assembler: Some("as".to_string()),
+                assembler_flags: Some(Vec::new()),
+                linker: None,
+                archiver: None,
+                target: Some(default_target()),
+                build_dir: None,
+                verbose: Some(false),
+                dry_run: Some(false),
+                deny_warnings: Some(false),
+                color: Some("auto".to_string()),
+                jobs: None,
+                profile: None,
+                workspace: None,
+                bin: None,
+                staticlib: None,
+                sharedlib: None,
+            },
+        }
+    }
+}
+
+/// The effective configuration, resolved from all layers.
+#[derive(Debug, Clone)]
+pub struct Config 
+{
+    pub input_files: Vec<String>,
+    pub output_file: String,
+    /// Extra flags for the linker (passed as-is)
+    pub extra_flags: Vec<String>,
+    pub assembler: String,
+    pub assembler_flags: Vec<String>,
+    /// Linker program; None uses the target's default.
+    pub linker: Option<String>,
+    /// Archiver program for static libraries; None uses "ar".
+    pub archiver: Option<String>,
+    pub target: String,
+    pub verbose: bool,
+    pub dry_run: bool,
+    /// Fail the build when the assembler or linker prints warnings.
+    pub deny_warnings: bool,
+    /// Directory for object files; None uses "target/rasm/<profile>".
+    pub build_dir: Option<String>,
+    /// Name of the selected build profile.
+    pub profile: String,
+    /// Profiles defined in configuration files.
+    pub profiles: BTreeMap<String, Profile>,
+    /// Whether to emit debug information (set by the profile).
+    pub debug: bool,
+    /// Whether to strip symbols from the output (set by the profile).
+    pub strip: bool,
+    /// Rebuild all files, even if they are up to date.
+    pub force: bool,
+    /// Assemble every file after a failure, to report all errors at once.
+    pub keep_going: bool,
+    /// How build progress is reported.
+    pub message_format: MessageFormat,
+    /// CI reports to write after a build.
+    pub reports: Vec<ReportRequest>,
+    /// Where to write the build's summary for the workspace's combined reports.
+    pub summary_file: Option<PathBuf>,
+    /// Project configuration file that was loaded, if any.
+    pub config_file: Option<String>,
+    pub color: String,
+    /// Number of parallel jobs; None uses one per CPU.
+    pub jobs: Option<usize>,
+    /// Tables from `[[bin]]`, `[[staticlib]]` and `[[sharedlib]]`, in that order.
+    pub artifact_tables: Vec<(ArtifactKind, ArtifactTable)>,
+}
+
+/// What kind of file an artifact is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArtifactKind 
+{
+    Bin,
+    StaticLib,
+    SharedLib,
+}
+
+impl ArtifactKind 
+{
+    /// Configuration key of the tables of this kind.
+    pub fn key(self) -> &'static str 
+    {
+        match self 
+        {
+            ArtifactKind::Bin => "bin",
+            ArtifactKind::StaticLib => "staticlib",
+            ArtifactKind::SharedLib => "sharedlib",
+        }
+    }
+}
+
+/// An artifact to build, with its output path resolved.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Artifact 
+{
+    pub kind: ArtifactKind,
+    pub name: String,
+    pub input_files: Vec<String>,
+    pub output_file: String,
+    /// Entry point symbol; None uses the target's.
+    pub entry: Option<String>,
+    /// Linker flags appended to the configuration's `extra_flags`.
+    pub extra_flags: Vec<String>,
+    /// Recorded name of a shared library; None uses the linker's default.
+    pub soname: Option<String>,
+    /// Symbols a shared library exports; None exports every global symbol.
+    pub exported_symbols: Option<Vec<String>>,
+}
+
+/// The layer each effective value came from, by configuration key.
+pub type Origins = BTreeMap<&'static str, Source>;
+
+/// Take the value of one key from the highest layer that sets it,
+/// recording that layer's source.
+fn pick<T>(
+    layers: &[Layer],
+    origins: &mut Origins,
+    key: &'static str,
+    get: impl Fn(&FileConfig) -> Option<T>,
+) -> Option<T> 
+{
+    layers.iter().rev().find_map(|layer| {
+        let value = get(&layer.values)?;
+        origins.insert(key, layer.source.clone());
+        Some(value)
+    })
+}
+
+/// Resolve configuration layers, lowest precedence first, into the
+/// effective configuration.
+/// 
+/// Each key takes its value from the highest layer that sets it, so a
+/// layer can override any lower one, including back to a default value.
+/// Profile tables are merged by name instead, key by key as with `extends`,
+/// so a higher layer only overrides the profile keys it sets.
+/// 
+/// # Returns
+/// The effective configuration and the origin of each value
+pub fn resolve(layers: &[Layer]) -> (Config, Origins) 
+{
+    let mut origins = Origins::new();
+    let o = &mut origins;
+
+    let mut profiles = BTreeMap::new();
+    for layer in layers 
+    {
+        for (name, profile) in layer.values.profile.iter().flatten() 
+        {
+            let merged = match profiles.remove(name) 
+            {
+                Some(base) => profile.clone().merge_onto(base),
+                None => profile.clone(),
+            };
+            profiles.insert(name.clone(), merged);
+        }
+    }
+
+    // Artifact tables come from the highest layer that has any, unless a
+    // higher layer names input or output files, which build a single binary.
+    let has_tables = |l: &FileConfig| l.bin.is_some() || l.staticlib.is_some() || l.sharedlib.is_some();
+    let names_files = |layer: &Layer| {
+        layer.source != Source::Default && (layer.values.input_files.is_some() || layer.values.output_file.is_some())
+    };
+    let table_layer = layers.iter().rposition(|layer| has_tables(&layer.values));
+    let file_layer = layers.iter().rposition(names_files);
+    let tables_from = table_layer.filter(|&i| file_layer.is_none_or(|j| j <= i));
+    let mut artifact_tables = Vec::new();
+    if let Some(i) = tables_from 
+    {
+        let values = &layers[i].values;
+        for (kind, tables) in [
+            (ArtifactKind::Bin, &values.bin),
+            (ArtifactKind::StaticLib, &values.staticlib),
+            (ArtifactKind::SharedLib, &values.sharedlib),
+        ] 
+        {
+            if let Some(tables) = tables 
+            {
+                o.insert(kind.key(), layers[i].source.clone());
+                artifact_tables.extend(tables.iter().map(|table| (kind, table.clone())));
+            }
+        }
+    }
+
+    // Sources named below the tables are overridden by them. Ones next to
+    // the tables, in the same file, are kept for `validate` to reject.
+    let source_layers = tables_from.map_or(layers, |i| &layers[i..]);
+
+    let config = Config 
+    {
+        input_files: pick(source_layers, o, "input_files", |l| l.input_files.clone()).unwrap_or_default(),
+        output_file: pick(source_layers, o, "output_file", |l| l.output_file.clone()).unwrap_or_default(),
+        extra_flags: pick(layers, o, "extra_flags", |l| l.extra_flags.clone()).unwrap_or_default(),
+        assembler: pick(layers, o, "assembler", |l| l.assembler.clone()).unwrap_or_else(|| "as".to_string()),
+        assembler_flags: pick(layers, o, "assembler_flags", |l| l.assembler_flags.clone()).unwrap_or_default(),
+        linker: pick(layers, o, "linker", |l| l.linker.clone()),
+        archiver: pick(layers, o, "archiver", |l| l.archiver.clone()),
+        target: pick(layers, o, "target", |l| l.target.clone()).unwrap_or_else(default_target),
+        verbose: pick(layers, o, "verbose", |l| l.verbose).unwrap_or(false),
+        dry_run: pick(layers, o, "dry_run", |l| l.dry_run).unwrap_or(false),
+        deny_warnings: pick(layers, o, "deny_warnings", |l| l.deny_warnings).unwrap_or(false),
+        build_dir: pick(layers, o, "build_dir", |l| l.build_dir.clone()),
+        profile: "dev".to_string(),
+        profiles,
+        debug: false,
+        strip: false,
+        force: false,
+        keep_going: false,
+        message_format: MessageFormat::Human,
+        reports: Vec::new(),
+        summary_file: None,
+        config_file: None,
+        color: pick(layers, o, "color", |l| l.color.clone()).unwrap_or_else(|| "auto".to_string()),
+        jobs: pick(layers, o, "jobs", |l| l.jobs),
+        artifact_tables,
+    };
+
+    (config, origins)
+}
+
+/// Format a list as a TOML array.
+fn toml_list(values: &[String]) -> String 
+{
+    toml::Value::Array(values.iter().cloned().map(toml::Value::String).collect()).to_string()
+}
+
+impl Config 
+{
+    /// Directory where object files are written.
+    /// Defaults to "target/rasm/<profile>".
+    pub fn build_dir(&self) -> String 
+    {
+        self.build_dir
+            .clone()
+            .unwrap_or_else(|| format!("target/rasm/{}", self.profile))
+    }
+
+    /// The artifacts to build: one per `[[bin]]`, `[[staticlib]]` and
+    /// `[[sharedlib]]` table, or a single executable from `input_files` and
+    /// `output_file` when there are none.
+    pub fn artifacts(&self) -> Vec<Artifact> 
+    {
+        if self.artifact_tables.is_empty() 
+        {
+            return vec![Artifact 
+            {
+                kind: ArtifactKind::Bin,
+                name: self.output_file.clone(),
+                input_files: self.input_files.clone(),
+                output_file: self.output_file.clone(),
+                entry: None,
+                extra_flags: Vec::new(),
+                soname: None,
+                exported_symbols: None,
+            }];
+        }
+
+        let format = crate::target::parse(&self.target).map_or(ObjectFormat::Elf, |target| target.format);
+        self.artifact_tables
+            .iter()
+            .map(|(kind, table)| {
+                let name = &table.name;
+                let default_output = match (kind, format) {
+                    (ArtifactKind::Bin, _) => name.clone(),
+                    (ArtifactKind::StaticLib, _) => format!("lib{}.a", name),
+                    (ArtifactKind::SharedLib, ObjectFormat::MachO) => format!("lib{}.dylib", name),
+                    (ArtifactKind::SharedLib, ObjectFormat::Coff) => format!("{}.dll", name),
+                    (ArtifactKind::SharedLib, ObjectFormat::Elf) => format!("lib{}.so", name),
+                };
+                Artifact {
+                    kind: *kind,
+                    name: name.clone(),
+                    input_files: table.input_files.clone(),
+                    output_file: table.output_file.clone().unwrap_or(default_output),
+                    entry: table.entry.clone(),
+                    extra_flags: table.extra_flags.clone(),
+                    soname: table.soname.clone(),
+                    exported_symbols: table.exported_symbols.clone(),
                 }
-            }),
-            verbose: value.verbose.unwrap_or(false),
-            dry_run: value.dry_run.unwrap_or(false),
-            clean: value.clean.unwrap_or(false),
-            config_file: None,
-            watch: value.watch.unwrap_or(false),
-            color: value.color.unwrap_or_else(|| "auto".to_string()),
-            completions: None,
+            })
+            .collect()
+    }
+
+    /// Apply the build profile named by `self.profile` on top of the
+    /// resolved configuration.
+    ///
+    /// Profile flags are appended to the base `assembler_flags` and
+    /// `extra_flags`. The profile's `build_dir` is only used when no
+    /// `build_dir` was set explicitly. Keys the profile changes are
+    /// recorded in `origins` as coming from it.
+    pub fn apply_profile(&mut self, origins: &mut Origins) -> Result<()> 
+    {
+        let profile = resolve_profile(&self.profile, &self.profiles)?;
+        let source = Source::Profile(self.profile.clone());
+
+        for (key, flags, extra) in [
+            ("assembler_flags", &mut self.assembler_flags, profile.assembler_flags),
+            ("extra_flags", &mut self.extra_flags, profile.extra_flags),
+        ] 
+        {
+            if let Some(extra) = extra.filter(|extra| !extra.is_empty()) 
+            {
+                flags.extend(extra);
+                origins.insert(key, source.clone());
+            }
+        }
+        self.debug = profile.debug.unwrap_or(false);
+        self.strip = profile.strip.unwrap_or(false);
+        if self.build_dir.is_none() && profile.build_dir.is_some() 
+        {
+            self.build_dir = profile.build_dir;
+            origins.insert("build_dir", source);
+        }
+        Ok(())
+    }
+
+    /// Effective values as TOML `key = value` pairs, for `rasm config show`.
+    pub fn entries(&self) -> Vec<(&'static str, String)> 
+    {
+        let string = |s: &str| toml::Value::String(s.to_string()).to_string();
+        let mut entries = Vec::new();
+        if self.artifact_tables.is_empty() 
+        {
+            entries.push(("input_files", toml_list(&self.input_files)));
+            entries.push(("output_file", string(&self.output_file)));
+        }
+        for kind in [ArtifactKind::Bin, ArtifactKind::StaticLib, ArtifactKind::SharedLib] 
+        {
+            let tables: Vec<&ArtifactTable> = self.artifact_tables
+                .iter()
+                .filter(|(k, _)| *k == kind)
+                .map(|(_, table)| table)
+                .collect();
+            if let (false, Ok(value)) = (tables.is_empty(), toml::Value::try_from(&tables)) 
+            {
+                entries.push((kind.key(), value.to_string()));
+            }
+        }
+        entries.push(("extra_flags", toml_list(&self.extra_flags)));
+        entries.push(("assembler", string(&self.assembler)));
+        entries.push(("assembler_flags", toml_list(&self.assembler_flags)));
+        if let Some(ref linker) = self.linker 
+        {
+            entries.push(("linker", string(linker)));
+        }
+        if let Some(ref archiver) = self.archiver 
+        {
+            entries.push(("archiver", string(archiver)));
+        }
+        entries.push(("target", string(&self.target)));
+        entries.push(("build_dir", string(&self.build_dir())));
+        entries.push(("verbose", self.verbose.to_string()));
+        entries.push(("dry_run", self.dry_run.to_string()));
+        entries.push(("deny_warnings", self.deny_warnings.to_string()));
+        entries.push(("color", string(&self.color)));
+        if let Some(jobs) = self.jobs 
+        {
+            entries.push(("jobs", jobs.to_string()));
         }
+        entries
+    }
+
+    /// Validate the configuration and return an error if invalid.
+    pub fn validate(&self) -> anyhow::Result<()> 
+    {
+        self.validate_sources()?;
+
+        let mut outputs = Vec::new();
+        for artifact in self.artifacts() 
+        {
+            if artifact.output_file.is_empty() 
+            {
+                return Err(anyhow::anyhow!(
+                    "Output file must be specified\nPass `-o <FILE>` or set `output_file` in .rasm.toml"
+                ));
+            }
+
+            // Validate output path
+            if artifact.output_file.contains("..") 
+            {
+                return Err(anyhow::anyhow!(
+                    "Output file path cannot contain '..': {}", 
+                    artifact.output_file
+                ));
+            }
+
+            if outputs.contains(&artifact.output_file) 
+            {
+                return Err(anyhow::anyhow!(
+                    "Two artifacts are written to {}\nGive one of them a different `name` or `output_file`",
+                    artifact.output_file
+                ));
+            }
+            outputs.push(artifact.output_file);
+        }
+
+        Ok(())
+    }
+
+    /// Validate everything needed to assemble, but not to link,
+    /// so that `rasm check` works without an output file.
+    pub fn validate_sources(&self) -> anyhow::Result<()> 
+    {
+        if self.artifact_tables.is_empty() && self.input_files.is_empty() 
+        {
+            return Err(anyhow::anyhow!(
+                "No input files specified\nPass them on the command line or set `input_files` in .rasm.toml"
+            ));
+        }
+
+        if let Some((kind, _)) = self.artifact_tables.first() 
+        {
+            let top_level = match (self.input_files.is_empty(), self.output_file.is_empty()) 
+            {
+                (false, false) => Some("`input_files` and `output_file`"),
+                (false, true) => Some("`input_files`"),
+                (true, false) => Some("`output_file`"),
+                (true, true) => None,
+            };
+            if let Some(keys) = top_level 
+            {
+                return Err(anyhow::anyhow!(
+                    "The configuration sets both top-level {} and [[{}]] tables, so the top-level sources would be ignored
+                     Move them into a [[bin]] table, or remove the tables",
+                    keys,
+                    kind.key()
+                ));
+            }
+        }
+
+        for (kind, table) in &self.artifact_tables 
+        {
+            let key = kind.key();
+            if table.name.is_empty() 
+            {
+                return Err(anyhow::anyhow!("Every [[{}]] table needs a `name`", key));
+            }
+            if table.input_files.is_empty() 
+            {
+                return Err(anyhow::anyhow!("[[{}]] `{}` has no `input_files`", key, table.name));
+            }
+            if *kind != ArtifactKind::Bin && table.entry.is_some() 
+            {
+                return Err(anyhow::anyhow!("[[{}]] `{}`: only executables have an `entry`", key, table.name));
+            }
+            if *kind != ArtifactKind::SharedLib && (table.soname.is_some() || table.exported_symbols.is_some()) 
+            {
+                return Err(anyhow::anyhow!(
+                    "[[{}]] `{}`: only shared libraries have a `soname` and `exported_symbols`",
+                    key,
+                    table.name
+                ));
+            }
+            if *kind == ArtifactKind::StaticLib && !table.extra_flags.is_empty() 
+            {
+                return Err(anyhow::anyhow!(
+                    "[[staticlib]] `{}`: static libraries are not linked, so they take no `extra_flags`",
+                    table.name
+                ));
+            }
+        }
+        
+        if self.assembler.is_empty() 
+        {
+            return Err(anyhow::anyhow!("Assembler command cannot be empty"));
+        }
+
+        crate::target::parse(&self.target)?;
+        
+        // Validate that input files don't have suspicious paths
+        for input in self.artifacts().iter().flat_map(|artifact| &artifact.input_files) 
+        {
+            if input.contains("..") 
+            {
+                return Err(anyhow::anyhow!(
+                    "Input file paths cannot contain '..': {}", 
+                    input
+                ));
+            }
+        }
+        
+        if self.jobs == Some(0) 
+        {
+            return Err(anyhow::anyhow!("Number of jobs must be at least 1"));
+        }
+
+        if self.build_dir().is_empty() || self.build_dir().contains("..") 
+        {
+            return Err(anyhow::anyhow!(
+                "Build directory must be non-empty and cannot contain '..': {}", 
+                self.build_dir()
+            ));
+        }
+        
+        Ok(())
     }
 }
 
-/// Load configuration from a TOML file.
+/// Load configuration from a TOML file, together with the files it
+/// `extends` and `include`s.
 /// 
 /// # Arguments
 /// * `path` - Path to the TOML configuration file
 /// 
 /// # Returns
-/// A Config struct with values loaded from the file
-pub fn load_config(path: &str) -> Result<Config> 
+/// The configuration layer defined by the file and its bases, or a
+/// `file:line:column` diagnostic for syntax errors, unknown keys and
+/// wrongly typed values
+pub fn load_config(path: &str) -> Result<FileConfig> 
+{
+    load_with_bases(Path::new(path), &mut Vec::new())
+}
+
+/// Parse a single TOML configuration file.
+fn parse_config(path: &Path) -> Result<FileConfig> 
 {
-    let contents = fs::read_to_string(path)?;
-    let file_config: FileConfig = toml::from_str(&contents)?;
-    Ok(file_config.into())
+    let display = path.to_string_lossy();
+    let contents = fs::read_to_string(path).with_context(|| format!("Failed to read {}", display))?;
+    toml::from_str(&contents).map_err(|err| crate::schema::config_error(&display, &contents, &err))
 }
 
-/// Auto-detect configuration file in current directory.
-/// Searches for .rasm.toml, rasm.toml, .rasm/config.toml in order.
+/// Load `path` and merge it on top of its `extends` file and then its
+/// `include` files, in order. `chain` holds the files being loaded, to
+/// detect cycles.
+fn load_with_bases(path: &Path, chain: &mut Vec<PathBuf>) -> Result<FileConfig> 
+{
+    let canonical = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    if chain.contains(&canonical) 
+    {
+        let cycle: Vec<String> = chain
+            .iter()
+            .skip_while(|p| **p != canonical)
+            .chain(std::iter::once(&canonical))
+            .map(|p| p.display().to_string())
+            .collect();
+        return Err(anyhow::anyhow!("Config file includes itself: {}", cycle.join(" -> ")));
+    }
+
+    let file = parse_config(path)?;
+    chain.push(canonical);
+
+    let dir = path.parent().unwrap_or(Path::new(""));
+    let bases = file.extends.iter().chain(file.include.iter().flatten());
+    let mut merged = FileConfig::default();
+    for base in bases 
+    {
+        let base_config = load_with_bases(&dir.join(base), chain)
+            .with_context(|| format!("Failed to load {} (from {})", base, path.display()))?;
+        merged = base_config.merge_onto(merged);
+    }
+
+    chain.pop();
+    Ok(file.merge_onto(merged))
+}
+
+/// Sets one configuration key from the value of an environment variable.
+type EnvSetter = fn(&mut FileConfig, &str) -> Result<()>;
+
+/// Split a list-valued environment variable on whitespace.
+fn env_list(value: &str) -> Vec<String> 
+{
+    value.split_whitespace().map(str::to_string).collect()
+}
+
+/// Parse a boolean environment variable, accepting the usual spellings.
+fn env_bool(value: &str) -> Result<bool> 
+{
+    match value.to_ascii_lowercase().as_str() 
+    {
+        "1" | "true" | "yes" | "on" => Ok(true),
+        "0" | "false" | "no" | "off" => Ok(false),
+        _ => Err(anyhow::anyhow!("expected true or false, found '{}'", value)),
+    }
+}
+
+/// Environment variables that override the project configuration file,
+/// one per key. List values are separated by whitespace.
+const RASM_ENV_VARS: &[(&str, EnvSetter)] = &[
+    ("RASM_INPUT_FILES", |c, v| { c.input_files = Some(env_list(v)); Ok(()) }),
+    ("RASM_OUTPUT_FILE", |c, v| { c.output_file = Some(v.to_string()); Ok(()) }),
+    ("RASM_EXTRA_FLAGS", |c, v| { c.extra_flags = Some(env_list(v)); Ok(()) }),
+    ("RASM_ASSEMBLER", |c, v| { c.assembler = Some(v.to_string()); Ok(()) }),
+    ("RASM_ASSEMBLER_FLAGS", |c, v| { c.assembler_flags = Some(env_list(v)); Ok(()) }),
+    ("RASM_LINKER", |c, v| { c.linker = Some(v.to_string()); Ok(()) }),
+    ("RASM_ARCHIVER", |c, v| { c.archiver = Some(v.to_string()); Ok(()) }),
+    ("RASM_TARGET", |c, v| { c.target = Some(v.to_string()); Ok(()) }),
+    ("RASM_BUILD_DIR", |c, v| { c.build_dir = Some(v.to_string()); Ok(()) }),
+    ("RASM_VERBOSE", |c, v| { c.verbose = Some(env_bool(v)?); Ok(()) }),
+    ("RASM_DRY_RUN", |c, v| { c.dry_run = Some(env_bool(v)?); Ok(()) }),
+    ("RASM_DENY_WARNINGS", |c, v| { c.deny_warnings = Some(env_bool(v)?); Ok(()) }),
+    ("RASM_COLOR", |c, v| { c.color = Some(v.to_string()); Ok(()) }),
+    ("RASM_JOBS", |c, v| { c.jobs = Some(v.parse()?); Ok(()) }),
+];
+
+/// Conventional toolchain variables, as used by make. Like make's defaults,
+/// they only apply where the project configuration file says nothing.
+const STANDARD_ENV_VARS: &[(&str, EnvSetter)] = &[
+    ("AS", |c, v| { c.assembler = Some(v.to_string()); Ok(()) }),
+    ("ASFLAGS", |c, v| { c.assembler_flags = Some(env_list(v)); Ok(()) }),
+    ("LD", |c, v| { c.linker = Some(v.to_string()); Ok(()) }),
+    ("LDFLAGS", |c, v| { c.extra_flags = Some(env_list(v)); Ok(()) }),
+    ("AR", |c, v| { c.archiver = Some(v.to_string()); Ok(()) }),
+];
+
+/// Build one layer per environment variable in `vars` that is set and
+/// non-empty, so that `rasm config show --origin` can name the variable.
+fn env_layers(vars: &[(&'static str, EnvSetter)], get: &dyn Fn(&str) -> Option<String>) -> Result<Vec<Layer>> 
+{
+    let mut layers = Vec::new();
+    for (var, set) in vars 
+    {
+        let Some(value) = get(var).filter(|v| !v.trim().is_empty()) else {
+            continue;
+        };
+        let mut values = FileConfig::default();
+        set(&mut values, value.trim()).with_context(|| format!("Invalid value for {}", var))?;
+        layers.push(Layer { source: Source::Env(var), values });
+    }
+    Ok(layers)
+}
+
+/// Layers from the `RASM_*` environment variables, which override the
+/// project configuration file.
+/// 
+/// # Arguments
+/// * `get` - Environment lookup, usually `std::env::var`
+pub fn rasm_env_layers(get: &dyn Fn(&str) -> Option<String>) -> Result<Vec<Layer>> 
+{
+    env_layers(RASM_ENV_VARS, get)
+}
+
+/// Layers from the standard `AS`, `ASFLAGS`, `LD`, `LDFLAGS` and `AR` variables,
+/// which sit below the project configuration file.
+/// 
+/// # Arguments
+/// * `get` - Environment lookup, usually `std::env::var`
+pub fn standard_env_layers(get: &dyn Fn(&str) -> Option<String>) -> Result<Vec<Layer>> 
+{
+    env_layers(STANDARD_ENV_VARS, get)
+}
+
+/// Machine-wide configuration file, loaded below the user's.
+pub const SYSTEM_CONFIG: &str = "/etc/rasm/config.toml";
+
+/// Path of the per-user configuration file: `$XDG_CONFIG_HOME/rasm/config.toml`,
+/// or `~/.config/rasm/config.toml` when `XDG_CONFIG_HOME` is unset.
+/// 
+/// # Arguments
+/// * `xdg_config_home` - Value of `XDG_CONFIG_HOME`
+/// * `home` - Value of `HOME`
+pub fn user_config_path(xdg_config_home: Option<PathBuf>, home: Option<PathBuf>) -> Option<PathBuf> 
+{
+    // Relative values are invalid per the XDG spec and are ignored.
+    let config_home = xdg_config_home
+        .filter(|dir| dir.is_absolute())
+        .or_else(|| home.map(|home| home.join(".config")))?;
+    Some(config_home.join("rasm").join("config.toml"))
+}
+
+/// Load the machine-wide and per-user configuration files that exist,
+/// lowest precedence first. These hold settings shared by every project,
+/// such as the preferred assembler, linker, color and job count.
 /// 
 /// # Returns
-/// Path to the configuration file if found, None otherwise
-pub fn auto_detect_config() -> Option<String> 
+/// One layer per file found
+pub fn shared_layers() -> Result<Vec<Layer>> 
+{
+    let user = user_config_path(
+        std::env::var_os("XDG_CONFIG_HOME").map(PathBuf::from),
+        std::env::var_os("HOME").map(PathBuf::from),
+    );
+    let mut layers = Vec::new();
+
+    for path in std::iter::once(PathBuf::from(SYSTEM_CONFIG)).chain(user) 
+    {
+        if !path.is_file() 
+        {
+            continue;
+        }
+        let path = path.to_string_lossy().into_owned();
+        let values = load_config(&path).with_context(|| format!("Failed to load {}", path))?;
+        layers.push(Layer { source: Source::File(path), values });
+    }
+
+    Ok(layers)
+}
+
+/// Project configuration file names, in order of precedence.
+pub const CONFIG_FILE_NAMES: &[&str] = &[".rasm.toml", "rasm.toml", ".rasm/config.toml"];
+
+/// Directory a configuration file's paths are relative to: the directory
+/// containing it, or the project directory for `.rasm/config.toml`.
+pub fn project_root(config_file: &str) -> PathBuf 
+{
+    let mut dir = Path::new(config_file).parent();
+    if dir.and_then(Path::file_name).is_some_and(|name| name == ".rasm") 
+    {
+        dir = dir.and_then(Path::parent);
+    }
+    match dir 
+    {
+        Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+        _ => PathBuf::from("."),
+    }
+}
+
+/// Path of `cwd` relative to the project `root`, which command-line paths
+/// are rebased onto once RASM has changed into the root. Falls back to the
+/// absolute `cwd` when it lies outside the project.
+pub fn project_base(root: &Path, cwd: &Path) -> PathBuf 
+{
+    cwd.strip_prefix(root)
+        .map(Path::to_path_buf)
+        .unwrap_or_else(|_| cwd.to_path_buf())
+}
+
+/// Directories that mark the root of a version-controlled checkout,
+/// where the search for a configuration file stops.
+const VCS_MARKERS: &[&str] = &[".git", ".hg", ".svn", ".jj"];
+
+/// Find the configuration file for a project containing `dir`.
+/// Checks `dir` and then each parent directory for the names in
+/// `CONFIG_FILE_NAMES`, stopping after the version-control root or the
+/// filesystem root.
+/// 
+/// # Arguments
+/// * `dir` - Directory to start searching from
+/// 
+/// # Returns
+/// Path to the configuration file relative to `dir` (e.g. "../.rasm.toml"),
+/// or None if no configuration file was found
+pub fn find_config(dir: &Path) -> Option<PathBuf> 
 {
-    let candidates = vec![
-        ".rasm.toml",
-        "rasm.toml",
-        ".rasm/config.toml",
-    ];
-    
-    for candidate in candidates 
+    let dir = dir.canonicalize().ok()?;
+    let mut up = PathBuf::new();
+
+    for ancestor in dir.ancestors() 
     {
-        if std::path::Path::new(candidate).exists() 
+        for name in CONFIG_FILE_NAMES 
         {
-            return Some(candidate.to_string());
+            if ancestor.join(name).is_file() 
+            {
+                return Some(up.join(name));
+            }
         }
+        if VCS_MARKERS.iter().any(|marker| ancestor.join(marker).exists()) 
+        {
+            break;
+        }
+        up.push("..");
     }
-    
+
     None
 }
+
+/// Auto-detect the configuration file for the current directory.
+/// Searches for .rasm.toml, rasm.toml, .rasm/config.toml in the current
+/// directory and its parents, as described in `find_config`.
+/// 
+/// # Returns
+/// Path to the configuration file if found, None otherwise
+pub fn auto_detect_config() -> Option<String> 
+{
+    let cwd = std::env::current_dir().ok()?;
+    find_config(&cwd).map(|path| path.to_string_lossy().into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_test_config() -> Config {
+        Config {
+            input_files: vec!["test.s".to_string()],
+            output_file: "test.out".to_string(),
+            extra_flags: vec![],
+            assembler: "as".to_string(),
+            assembler_flags: vec![],
+            linker: None,
+            archiver: None,
+            target: "x86_64".to_string(),
+            verbose: false,
+            dry_run: false,
+            deny_warnings: false,
+            build_dir: None,
+            profile: "dev".to_string(),
+            profiles: BTreeMap::new(),
+            debug: false,
+            strip: false,
+            force: false,
+            keep_going: false,
+            message_format: MessageFormat::Human,
+            reports: Vec::new(),
+            summary_file: None,
+            config_file: None,
+            color: "auto".to_string(),
+            jobs: None,
+            artifact_tables: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_validate_valid_config() {
+        let config = create_test_config();
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_validate_empty_input_files() {
+        let mut config = create_test_config();
+        config.input_files = vec![];
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_validate_empty_output_file() {
+        let mut config = create_test_config();
+        config.output_file = String::new();
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_validate_path_traversal_input() {
+        let mut config = create_test_config();
+        config.input_files = vec!["../evil.s".to_string()];
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_validate_path_traversal_output() {
+        let mut config = create_test_config();
+        config.output_file = "../evil.out".to_string();
+        assert!(config.validate().is_err());
+    }
+
+    fn file_layer(path: &str, values: FileConfig) -> Layer {
+        Layer { source: Source::File(path.to_string()), values }
+    }
+
+    fn cli_layer(values: FileConfig) -> Layer {
+        Layer { source: Source::Cli, values }
+    }
+
+    #[test]
+    fn test_resolve_layers() {
+        let file = FileConfig {
+            input_files: Some(vec!["new.s".to_string()]),
+            verbose: Some(true),
+            ..FileConfig::default()
+        };
+        let (config, origins) = resolve(&[Layer::defaults(), file_layer(".rasm.toml", file)]);
+
+        assert_eq!(config.input_files, vec!["new.s".to_string()]);
+        assert!(config.verbose);
+        assert_eq!(config.assembler, "as");
+        assert_eq!(origins["verbose"], Source::File(".rasm.toml".to_string()));
+        assert_eq!(origins["assembler"], Source::Default);
+    }
+
+    #[test]
+    fn test_cli_can_override_back_to_defaults() {
+        let file = FileConfig {
+            assembler: Some("nasm".to_string()),
+            verbose: Some(true),
+            color: Some("always".to_string()),
+            ..FileConfig::default()
+        };
+        let cli = FileConfig {
+            assembler: Some("as".to_string()),
+            verbose: Some(false),
+            color: Some("auto".to_string()),
+            ..FileConfig::default()
+        };
+        let (config, origins) = resolve(&[Layer::defaults(), file_layer(".rasm.toml", file), cli_layer(cli)]);
+
+        assert_eq!(config.assembler, "as");
+        assert!(!config.verbose);
+        assert_eq!(config.color, "auto");
+        assert_eq!(origins["assembler"], Source::Cli);
+    }
+
+    #[test]
+    fn test_profiles_merge_by_name() {
+        let mut base = BTreeMap::new();
+        base.insert("ci".to_string(), Profile { debug: Some(true), ..Profile::default() });
+        base.insert(
+            "release".to_string(),
+            Profile { strip: Some(false), extra_flags: Some(vec!["-O1".to_string()]), ..Profile::default() },
+        );
+        let mut over = BTreeMap::new();
+        over.insert(
+            "release".to_string(),
+            Profile { debug: Some(true), extra_flags: Some(vec!["--gc-sections".to_string()]), ..Profile::default() },
+        );
+
+        let (config, _) = resolve(&[
+            file_layer("base.toml", FileConfig { profile: Some(base), ..FileConfig::default() }),
+            file_layer(".rasm.toml", FileConfig { profile: Some(over), ..FileConfig::default() }),
+        ]);
+
+        assert_eq!(config.profiles.len(), 2);
+        assert_eq!(config.profiles["release"].debug, Some(true));
+        assert_eq!(config.profiles["release"].strip, Some(false));
+        assert_eq!(config.profiles["release"].extra_flags, Some(vec!["-O1".to_string(), "--gc-sections".to_string()]));
+    }
+
+    #[test]
+    fn test_profile_values_and_origins() {
+        let mut profiles = BTreeMap::new();
+        profiles.insert(
+            "release".to_string(),
+            Profile {
+                build_dir: Some("out/rel".to_string()),
+                assembler_flags: Some(vec!["--64".to_string()]),
+                ..Profile::default()
+            },
+        );
+        let file = FileConfig { profile: Some(profiles), ..FileConfig::default() };
+        let (mut config, mut origins) = resolve(&[Layer::defaults(), file_layer(".rasm.toml", file)]);
+        config.profile = "release".to_string();
+        config.apply_profile(&mut origins).unwrap();
+
+        assert_eq!(config.build_dir(), "out/rel");
+        assert_eq!(config.assembler_flags, vec!["--64"]);
+        assert_eq!(origins["build_dir"].to_string(), "profile.release");
+        assert_eq!(origins["assembler_flags"], Source::Profile("release".to_string()));
+        assert_eq!(origins["extra_flags"], Source::Default);
+    }
+
+    #[test]
+    fn test_find_config_walks_up_to_vcs_root() {
+        let root = std::env::temp_dir().join(format!("rasm-find-config-{}", std::process::id()));
+        let project = root.join("project");
+        let nested = project.join("src/arch/x86");
+        fs::create_dir_all(&nested).unwrap();
+        fs::create_dir_all(project.join(".git")).unwrap();
+        fs::write(root.join(".rasm.toml"), "").unwrap();
+
+        // The file above the repository root is not part of the project.
+        assert_eq!(find_config(&nested), None);
+
+        fs::write(project.join("rasm.toml"), "").unwrap();
+        assert_eq!(find_config(&nested), Some(PathBuf::from("../../../rasm.toml")));
+        assert_eq!(find_config(&project), Some(PathBuf::from("rasm.toml")));
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn test_project_root() {
+        assert_eq!(project_root(".rasm.toml"), PathBuf::from("."));
+        assert_eq!(project_root("../../rasm.toml"), PathBuf::from("../.."));
+        assert_eq!(project_root("../.rasm/config.toml"), PathBuf::from(".."));
+        assert_eq!(project_root(".rasm/config.toml"), PathBuf::from("."));
+    }
+
+    #[test]
+    fn test_rebase_cli_paths_onto_project_root() {
+        let mut layer = FileConfig {
+            input_files: Some(vec!["main.s".to_string(), "/abs/lib.s".to_string()]),
+            output_file: Some("app".to_string()),
+            assembler: Some("nasm".to_string()),
+            ..FileConfig::default()
+        };
+        let base = project_base(Path::new("/work/project"), Path::new("/work/project/src"));
+        layer.rebase(&base);
+
+        assert_eq!(layer.input_files, Some(vec!["src/main.s".to_string(), "/abs/lib.s".to_string()]));
+        assert_eq!(layer.output_file.as_deref(), Some("src/app"));
+        assert_eq!(layer.build_dir, None);
+        assert_eq!(layer.assembler.as_deref(), Some("nasm"));
+        assert_eq!(project_base(Path::new("/work/project"), Path::new("/tmp")), PathBuf::from("/tmp"));
+    }
+
+    #[test]
+    fn test_user_config_path() {
+        assert_eq!(
+            user_config_path(Some("/xdg".into()), Some("/home/me".into())),
+            Some(PathBuf::from("/xdg/rasm/config.toml"))
+        );
+        assert_eq!(
+            user_config_path(Some("relative".into()), Some("/home/me".into())),
+            Some(PathBuf::from("/home/me/.config/rasm/config.toml"))
+        );
+        assert_eq!(user_config_path(None, None), None);
+    }
+
+    #[test]
+    fn test_project_overrides_user_defaults() {
+        let user = FileConfig {
+            assembler: Some("nasm".to_string()),
+            linker: Some("mold".to_string()),
+            jobs: Some(4),
+            ..FileConfig::default()
+        };
+        let project = FileConfig { linker: Some("ld".to_string()), ..FileConfig::default() };
+        let (config, origins) = resolve(&[
+            Layer::defaults(),
+            file_layer("/etc/rasm/config.toml", FileConfig { jobs: Some(2), ..FileConfig::default() }),
+            file_layer("/home/me/.config/rasm/config.toml", user),
+            file_layer(".rasm.toml", project),
+        ]);
+
+        assert_eq!(config.assembler, "nasm");
+        assert_eq!(config.linker.as_deref(), Some("ld"));
+        assert_eq!(config.jobs, Some(4));
+        assert_eq!(origins["jobs"], Source::File("/home/me/.config/rasm/config.toml".to_string()));
+    }
+
+    #[test]
+    fn test_artifact_tables() {
+        let project: FileConfig = toml::from_str(
+            r#"
+            target = "x86_64-unknown-linux-gnu"
+
+            [[bin]]
+            name = "tool_a"
+            input_files = ["tool_a.s", "string_utils.s"]
+            entry = "main"
+
+            [[bin]]
+            name = "tool_b"
+            input_files = ["tool_b.s", "string_utils.s"]
+
+            [[sharedlib]]
+            name = "strings"
+            input_files = ["string_utils.s"]
+            "#,
+        )
+        .unwrap();
+        let (config, origins) = resolve(&[Layer::defaults(), file_layer(".rasm.toml", project.clone())]);
+        let outputs: Vec<(ArtifactKind, String)> =
+            config.artifacts().into_iter().map(|a| (a.kind, a.output_file)).collect();
+        assert_eq!(
+            outputs,
+            vec![
+                (ArtifactKind::Bin, "tool_a".to_string()),
+                (ArtifactKind::Bin, "tool_b".to_string()),
+                (ArtifactKind::SharedLib, "libstrings.so".to_string()),
+            ]
+        );
+        assert_eq!(config.artifacts()[0].entry.as_deref(), Some("main"));
+        assert_eq!(origins["sharedlib"], Source::File(".rasm.toml".to_string()));
+        assert!(config.validate().is_ok());
+
+        // Input files on the command line build a single binary instead.
+        let cli = FileConfig {
+            input_files: Some(vec!["scratch.s".to_string()]),
+            output_file: Some("scratch".to_string()),
+            ..FileConfig::default()
+        };
+        let (config, _) = resolve(&[
+            Layer::defaults(),
+            file_layer(".rasm.toml", project.clone()),
+            Layer { source: Source::Cli, values: cli },
+        ]);
+        assert_eq!(config.artifacts().len(), 1);
+        assert_eq!(config.artifacts()[0].input_files, vec!["scratch.s"]);
+
+        let mut config = create_test_config();
+        config.input_files.clear();
+        config.output_file.clear();
+        config.artifact_tables = vec![(
+            ArtifactKind::StaticLib,
+            ArtifactTable { name: "strings".to_string(), entry: Some("main".to_string()), ..ArtifactTable::default() },
+        )];
+        assert!(config.validate().unwrap_err().to_string().contains("has no `input_files`"));
+        config.artifact_tables[0].1.input_files = vec!["string_utils.s".to_string()];
+        assert!(config.validate().unwrap_err().to_string().contains("only executables have an `entry`"));
+    }
+
+    #[test]
+    fn test_top_level_sources_next_to_tables() {
+        let project: FileConfig = toml::from_str(
+            r#"
+            input_files = ["main.s"]
+
+            [[staticlib]]
+            name = "strings"
+            input_files = ["string_utils.s"]
+            "#,
+        )
+        .unwrap();
+        let (config, _) = resolve(&[Layer::defaults(), file_layer(".rasm.toml", project)]);
+        let err = config.validate().unwrap_err().to_string();
+        assert!(err.contains("top-level `input_files` and [[staticlib]] tables"), "{}", err);
+
+        // Sources from a lower layer are overridden by the tables instead.
+        let shared = FileConfig { output_file: Some("app".to_string()), ..FileConfig::default() };
+        let tables = FileConfig {
+            bin: Some(vec![ArtifactTable {
+                name: "tool".to_string(),
+                input_files: vec!["tool.s".to_string()],
+                ..ArtifactTable::default()
+            }]),
+            ..FileConfig::default()
+        };
+        let (config, origins) = resolve(&[
+            Layer::defaults(),
+            file_layer("/etc/rasm/config.toml", shared),
+            file_layer(".rasm.toml", tables),
+        ]);
+        assert!(config.validate().is_ok());
+        assert!(!origins.contains_key("output_file"));
+        assert!(config.entries().iter().all(|(key, _)| *key != "output_file"));
+    }
+
+    fn env(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+        let vars: Vec<(String, String)> = vars.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+        move |name| vars.iter().find(|(k, _)| k == name).map(|(_, v)| v.clone())
+    }
+
+    #[test]
+    fn test_env_layers() {
+        let get = env(&[
+            ("RASM_ASSEMBLER", "llvm-mc"),
+            ("RASM_ASSEMBLER_FLAGS", " -g  -Iinc "),
+            ("RASM_JOBS", "3"),
+            ("RASM_DRY_RUN", "yes"),
+            ("RASM_TARGET", ""),
+            ("AS", "nasm"),
+            ("LD", "mold"),
+        ]);
+        let project = FileConfig { linker: Some("ld".to_string()), ..FileConfig::default() };
+
+        let mut layers = vec![Layer::defaults()];
+        layers.extend(standard_env_layers(&get).unwrap());
+        layers.push(file_layer(".rasm.toml", project));
+        layers.extend(rasm_env_layers(&get).unwrap());
+        let (config, origins) = resolve(&layers);
+
+        assert_eq!(config.assembler, "llvm-mc");
+        assert_eq!(config.assembler_flags, vec!["-g", "-Iinc"]);
+        assert_eq!(config.jobs, Some(3));
+        assert!(config.dry_run);
+        assert_eq!(origins["target"], Source::Default);
+        // LD only fills in what the project file leaves unset.
+        assert_eq!(config.linker.as_deref(), Some("ld"));
+        assert_eq!(origins["assembler"], Source::Env("RASM_ASSEMBLER"));
+    }
+
+    #[test]
+    fn test_env_layers_reject_bad_values() {
+        let err = rasm_env_layers(&env(&[("RASM_JOBS", "many")])).unwrap_err();
+        assert!(err.to_string().contains("RASM_JOBS"));
+        assert!(rasm_env_layers(&env(&[("RASM_VERBOSE", "maybe")])).is_err());
+    }
+
+    #[test]
+    fn test_extends_and_include() {
+        let dir = std::env::temp_dir().join(format!("rasm-extends-{}", std::process::id()));
+        fs::create_dir_all(dir.join("common")).unwrap();
+        fs::create_dir_all(dir.join("app")).unwrap();
+        fs::write(
+            dir.join("common/base.toml"),
+            "assembler = \"nasm\"\nassembler_flags = [\"-Icommon\"]\ninput_files = [\"base.s\"]\n\
+             [profile.release]\nextra_flags = [\"-s\"]\n",
+        )
+        .unwrap();
+        fs::write(dir.join("common/lto.toml"), "extra_flags = [\"--gc-sections\"]\nlinker = \"mold\"\n").unwrap();
+        fs::write(
+            dir.join("app/.rasm.toml"),
+            "extends = \"../common/base.toml\"\ninclude = [\"../common/lto.toml\"]\n\
+             input_files = [\"main.s\"]\nassembler_flags = [\"-g\"]\nextra_flags = [\"-lc\"]\n\
+             [profile.release]\nextra_flags = [\"-O2\"]\ndebug = true\n",
+        )
+        .unwrap();
+
+        let config = load_config(dir.join("app/.rasm.toml").to_str().unwrap()).unwrap();
+        assert_eq!(config.assembler.as_deref(), Some("nasm"));
+        assert_eq!(config.linker.as_deref(), Some("mold"));
+        assert_eq!(config.input_files, Some(vec!["main.s".to_string()]));
+        assert_eq!(config.assembler_flags, Some(vec!["-Icommon".to_string(), "-g".to_string()]));
+        assert_eq!(config.extra_flags, Some(vec!["--gc-sections".to_string(), "-lc".to_string()]));
+        let release = &config.profile.as_ref().unwrap()["release"];
+        assert_eq!(release.extra_flags, Some(vec!["-s".to_string(), "-O2".to_string()]));
+        assert_eq!(release.debug, Some(true));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_include_cycle() {
+        let dir = std::env::temp_dir().join(format!("rasm-include-cycle-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("a.toml"), "include = [\"b.toml\"]\n").unwrap();
+        fs::write(dir.join("b.toml"), "extends = \"a.toml\"\n").unwrap();
+
+        let err = load_config(dir.join("a.toml").to_str().unwrap()).unwrap_err();
+        assert!(format!("{:#}", err).contains("includes itself"), "{:#}", err);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_default_target() {
+        let target = default_target();
+        #[cfg(all(target_os = "macos", target_arch = "aarch64"))]
+        assert_eq!(target, "aarch64-apple-darwin");
+        
+        #[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+        assert_eq!(target, "x86_64-unknown-linux-gnu");
+
+        assert!(crate::target::parse(&target).is_ok());
+    }
+
+    #[test]
+    fn test_builtin_profiles() {
+        let profiles = BTreeMap::new();
+        let dev = resolve_profile("dev", &profiles).unwrap();
+        let release = resolve_profile("release", &profiles).unwrap();
+        assert_eq!((dev.debug, dev.strip), (Some(true), Some(false)));
+        assert_eq!((release.debug, release.strip), (Some(false), Some(true)));
+        assert!(resolve_profile("bench", &profiles).is_err());
+    }
+
+    #[test]
+    fn test_profile_tables_from_toml() {
+        let file: FileConfig = toml::from_str(
+            r#"
+            [profile.release]
+            assembler_flags = ["--strip-local-absolute"]
+
+            [profile.profiling]
+            inherits = "release"
+            debug = true
+            build_dir = "out/profiling"
+            "#,
+        )
+        .unwrap();
+        let profiles = file.profile.unwrap();
+
+        let release = resolve_profile("release", &profiles).unwrap();
+        assert_eq!(release.assembler_flags, Some(vec!["--strip-local-absolute".to_string()]));
+        assert_eq!(release.strip, Some(true));
+
+        let profiling = resolve_profile("profiling", &profiles).unwrap();
+        assert_eq!(profiling.debug, Some(true));
+        assert_eq!(profiling.strip, Some(true));
+        assert_eq!(profiling.build_dir.as_deref(), Some("out/profiling"));
+        assert_eq!(profiling.assembler_flags, release.assembler_flags);
+    }
+
+    #[test]
+    fn test_profile_inheritance_cycle() {
+        let mut profiles = BTreeMap::new();
+        profiles.insert("a".to_string(), Profile { inherits: Some("b".to_string()), ..Profile::default() });
+        profiles.insert("b".to_string(), Profile { inherits: Some("a".to_string()), ..Profile::default() });
+        let err = resolve_profile("a", &profiles).unwrap_err().to_string();
+        assert!(err.contains("a -> b -> a"));
+    }
+}
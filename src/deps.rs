@@ -0,0 +1,207 @@
+use crate::config::Config;
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Kind of dependency a directive introduces.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DirectiveKind
+{
+    /// Source text that is itself scanned for further directives
+    /// (GAS `.include`, NASM `%include`).
+    Include,
+    /// Binary data pulled in verbatim (GAS `.incbin`, NASM `incbin`).
+    Incbin,
+}
+
+/// Extract include-style directives from assembly source text.
+///
+/// Recognizes GAS `.include`/`.incbin` and NASM `%include`/`incbin`,
+/// optionally preceded by a label, with single- or double-quoted paths.
+pub fn parse_directives(text: &str) -> Vec<(DirectiveKind, String)>
+{
+    let mut found = Vec::new();
+
+    for line in text.lines()
+    {
+        let mut rest = line.trim_start();
+
+        // Skip a leading label such as `data: incbin "blob.bin"`.
+        if let Some(colon) = rest.find(':')
+        {
+            let label = &rest[..colon];
+            if !label.is_empty() && label.chars().all(|c| c.is_alphanumeric() || "_.$@?".contains(c))
+            {
+                rest = rest[colon + 1..].trim_start();
+            }
+        }
+
+        let keyword_len = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        let kind = match rest[..keyword_len].to_ascii_lowercase().as_str()
+        {
+            ".include" | "%include" => DirectiveKind::Include,
+            ".incbin" | "incbin" => DirectiveKind::Incbin,
+            _ => continue,
+        };
+
+        if let Some(path) = quoted(rest[keyword_len..].trim_start())
+        {
+            found.push((kind, path.to_string()));
+        }
+    }
+
+    found
+}
+
+/// Return the contents of a leading single- or double-quoted string.
+fn quoted(text: &str) -> Option<&str>
+{
+    let quote = text.chars().next().filter(|c| *c == '"' || *c == '\'')?;
+    let end = text[1..].find(quote)?;
+    Some(&text[1..1 + end])
+}
+
+/// Collect include search directories from the assembler flags
+/// (`-I dir`, `-Idir`, and NASM's `-i dir`/`-idir`).
+pub fn include_dirs(config: &Config) -> Vec<PathBuf>
+{
+    let mut dirs = Vec::new();
+    let mut flags = config.assembler_flags.iter();
+
+    while let Some(flag) = flags.next()
+    {
+        if flag == "-I" || flag == "-i"
+        {
+            if let Some(dir) = flags.next()
+            {
+                dirs.push(PathBuf::from(dir));
+            }
+        } else if let Some(dir) = flag.strip_prefix("-I").or_else(|| flag.strip_prefix("-i")) {
+            dirs.push(PathBuf::from(dir));
+        }
+    }
+
+    dirs
+}
+
+/// Resolve a directive path against the including file's directory,
+/// the working directory and the include search directories, in that order.
+fn resolve(path: &str, including_dir: &Path, include_dirs: &[PathBuf]) -> Option<PathBuf>
+{
+    std::iter::once(including_dir.join(path))
+        .chain(std::iter::once(PathBuf::from(path)))
+        .chain(include_dirs.iter().map(|dir| dir.join(path)))
+        .find(|candidate| candidate.is_file())
+}
+
+/// A file a source depends on, either found on disk or referenced but missing.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Dependency
+{
+    Found(PathBuf),
+    /// Kept so that creating the file later changes the cache key.
+    Missing(String),
+}
+
+/// Find every file `source` depends on, following includes transitively.
+///
+/// The result is sorted and deduplicated and does not contain `source` itself.
+/// Unreadable sources yield no dependencies; the assembler reports the error.
+pub fn scan(source: &Path, include_dirs: &[PathBuf]) -> Vec<Dependency>
+{
+    let mut deps = BTreeSet::new();
+    let mut pending = vec![source.to_path_buf()];
+    let mut visited = BTreeSet::new();
+
+    while let Some(file) = pending.pop()
+    {
+        if !visited.insert(file.clone())
+        {
+            continue;
+        }
+        let Ok(text) = fs::read_to_string(&file) else {
+            continue;
+        };
+        let dir = file.parent().unwrap_or(Path::new("")).to_path_buf();
+
+        for (kind, path) in parse_directives(&text)
+        {
+            match resolve(&path, &dir, include_dirs)
+            {
+                Some(found) => {
+                    if kind == DirectiveKind::Include
+                    {
+                        pending.push(found.clone());
+                    }
+                    deps.insert(Dependency::Found(found));
+                }
+                None => {
+                    deps.insert(Dependency::Missing(path));
+                }
+            }
+        }
+    }
+
+    deps.into_iter()
+        .filter(|dep| *dep != Dependency::Found(source.to_path_buf()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_gas_directives() {
+        let text = "  .include \"macros.inc\"\n.incbin \"font.bin\"\n.global _start\n";
+        assert_eq!(
+            parse_directives(text),
+            vec![
+                (DirectiveKind::Include, "macros.inc".to_string()),
+                (DirectiveKind::Incbin, "font.bin".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_nasm_directives() {
+        let text = "%include 'defs.inc'\nsprite: incbin \"sprite.raw\"\nINCBIN \"upper.bin\"\n";
+        assert_eq!(
+            parse_directives(text),
+            vec![
+                (DirectiveKind::Include, "defs.inc".to_string()),
+                (DirectiveKind::Incbin, "sprite.raw".to_string()),
+                (DirectiveKind::Incbin, "upper.bin".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_ignores_other_lines() {
+        let text = "mov x0, #1 // .include \"nope.inc\"\n; %include \"commented.inc\"\n";
+        assert!(parse_directives(text).is_empty());
+    }
+
+    #[test]
+    fn test_scan_follows_nested_includes() {
+        let dir = std::env::temp_dir().join(format!("rasm-deps-{}", std::process::id()));
+        fs::create_dir_all(dir.join("inc")).unwrap();
+        fs::write(dir.join("main.s"), ".include \"inc/a.inc\"\n.include \"gone.inc\"\n").unwrap();
+        fs::write(dir.join("inc/a.inc"), ".include \"b.inc\"\n").unwrap();
+        fs::write(dir.join("inc/b.inc"), ".incbin \"data.bin\"\n").unwrap();
+        fs::write(dir.join("inc/data.bin"), [0u8; 4]).unwrap();
+
+        let deps = scan(&dir.join("main.s"), &[]);
+        assert_eq!(
+            deps,
+            vec![
+                Dependency::Found(dir.join("inc/a.inc")),
+                Dependency::Found(dir.join("inc/b.inc")),
+                Dependency::Found(dir.join("inc/data.bin")),
+                Dependency::Missing("gone.inc".to_string()),
+            ]
+        );
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,617 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+
+/// How serious a diagnostic is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity
+{
+    Error,
+    Warning,
+    Note,
+}
+
+impl Severity
+{
+    /// Parse a severity word as printed by assemblers and linkers
+    /// ("Error", "warning", "fatal", "Info", ...).
+    fn from_word(word: &str) -> Option<Severity>
+    {
+        match word.to_ascii_lowercase().as_str()
+        {
+            "error" | "fatal" | "panic" => Some(Severity::Error),
+            "warning" => Some(Severity::Warning),
+            "note" | "info" | "remark" => Some(Severity::Note),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str
+    {
+        match self
+        {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+            Severity::Note => "note",
+        }
+    }
+}
+
+/// One message from an assembler or linker, with its location when known.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Diagnostic
+{
+    pub file: Option<String>,
+    /// 1-based line number.
+    pub line: Option<usize>,
+    /// 1-based column number.
+    pub column: Option<usize>,
+    pub severity: Severity,
+    /// The message. Lines after the first are details, such as where an
+    /// undefined symbol is referenced.
+    pub message: String,
+}
+
+impl Diagnostic
+{
+    /// A diagnostic without a location.
+    pub fn new(severity: Severity, message: impl Into<String>) -> Diagnostic
+    {
+        Diagnostic { file: None, line: None, column: None, severity, message: message.into() }
+    }
+
+    /// Set the location from a "file:line[:column]" prefix, or from a plain
+    /// file name with an optional "(.text+0x5)" section offset.
+    fn locate(&mut self, location: &str)
+    {
+        match parse_location(location)
+        {
+            Some((file, line, column)) => {
+                self.file = Some(file);
+                self.line = Some(line);
+                self.column = column;
+            }
+            None => {
+                let file = location.split('(').next().unwrap_or_default().trim();
+                self.file = (!file.is_empty()).then(|| file.to_string());
+            }
+        }
+    }
+
+    /// Append a detail line to the message.
+    fn add_detail(&mut self, detail: &str)
+    {
+        self.message.push('\n');
+        self.message.push_str(detail);
+    }
+}
+
+/// A failed assembler, linker or archiver run, with the messages it printed.
+#[derive(Debug)]
+pub struct ToolError
+{
+    /// The tool, as in "Assembler (gas)" or "Linker (ld)".
+    pub tool: String,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl fmt::Display for ToolError
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        write!(f, "{} failed:\n{}", self.tool, render(&self.diagnostics))
+    }
+}
+
+impl std::error::Error for ToolError {}
+
+/// The errors of every file that failed in a `--keep-going` build.
+#[derive(Debug)]
+pub struct Failures
+{
+    pub errors: Vec<anyhow::Error>,
+    /// Number of files that were attempted.
+    pub total: usize,
+}
+
+impl fmt::Display for Failures
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        // Messages from the same tool are rendered together, grouped by file.
+        let mut tools: Vec<(&str, Vec<Diagnostic>)> = Vec::new();
+        let mut others = Vec::new();
+        for error in &self.errors
+        {
+            match error.downcast_ref::<ToolError>()
+            {
+                Some(failed) => match tools.iter_mut().find(|(tool, _)| *tool == failed.tool)
+                {
+                    Some((_, diagnostics)) => diagnostics.extend(failed.diagnostics.iter().cloned()),
+                    None => tools.push((&failed.tool, failed.diagnostics.clone())),
+                },
+                None => others.push(error),
+            }
+        }
+        for (tool, diagnostics) in tools
+        {
+            writeln!(f, "{} failed:\n{}", tool, render(&diagnostics))?;
+        }
+        for error in others
+        {
+            writeln!(f, "{}", error)?;
+        }
+        write!(f, "{} of {} file(s) failed to assemble", self.errors.len(), self.total)
+    }
+}
+
+impl std::error::Error for Failures {}
+
+/// Split "path:line[:column]" into its parts. Column 0, which some tools
+/// print when they don't know the column, counts as no column.
+/// Returns None when there is no line number.
+fn parse_location(text: &str) -> Option<(String, usize, Option<usize>)>
+{
+    let (rest, last) = text.rsplit_once(':')?;
+    let last = last.parse::<usize>().ok()?;
+    match rest.rsplit_once(':')
+    {
+        Some((file, line)) if !file.is_empty() && line.parse::<usize>().is_ok() => {
+            Some((file.to_string(), line.parse().ok()?, Some(last).filter(|&column| column > 0)))
+        }
+        _ => (!rest.is_empty()).then(|| (rest.to_string(), last, None)),
+    }
+}
+
+/// Split "<location>: <severity>: <message>" at the severity word. The
+/// location is empty for messages such as GAS's "Error: can't open x.s".
+fn split_severity(line: &str) -> Option<(&str, Severity, &str)>
+{
+    if let Some((word, message)) = line.split_once(": ")
+    {
+        if let Some(severity) = Severity::from_word(word)
+        {
+            return Some(("", severity, message));
+        }
+    }
+
+    let mut search = 0;
+    while let Some(i) = line[search..].find(": ")
+    {
+        let start = search + i;
+        let rest = &line[start + 2..];
+        if let Some((word, message)) = rest.split_once(": ")
+        {
+            if let Some(severity) = Severity::from_word(word)
+            {
+                return Some((&line[..start], severity, message));
+            }
+        }
+        search = start + 2;
+    }
+    None
+}
+
+/// Whether `name` is a linker, compiler driver or archiver rather than a
+/// file, as in "/usr/bin/ld: cannot find -lfoo".
+fn is_tool(name: &str) -> bool
+{
+    const TOOLS: &[&str] = &[
+        "ld", "ld.bfd", "ld.gold", "gold", "ld.lld", "ld64.lld", "lld", "ld64", "mold", "collect2", "cc", "gcc",
+        "clang", "ar", "llvm-ar", "nasm", "yasm", "as", "llvm-mc", "fasm",
+    ];
+    let base = Path::new(name).file_name().map(|n| n.to_string_lossy()).unwrap_or_default();
+    base.starts_with("clang") || TOOLS.iter().any(|tool| base == *tool || base.ends_with(&format!("-{}", tool)))
+}
+
+/// Parse the messages of GAS, NASM, yasm and llvm-mc, which all print
+/// `file:line[:column]: severity: message`.
+///
+/// Lines that are not messages, such as GAS's "Assembler messages:" header
+/// and the source excerpt llvm-mc prints below each message, are skipped.
+/// Output without any recognizable message becomes a single error, so that
+/// nothing is lost.
+pub fn parse_assembler(output: &str) -> Vec<Diagnostic>
+{
+    let mut diagnostics = Vec::new();
+    for line in output.lines()
+    {
+        if let Some((location, severity, message)) = split_severity(line)
+        {
+            let mut diagnostic = Diagnostic::new(severity, message.trim());
+            if !is_tool(location)
+            {
+                diagnostic.locate(location);
+            }
+            diagnostics.push(diagnostic);
+        }
+    }
+
+    if diagnostics.is_empty() && !output.trim().is_empty()
+    {
+        diagnostics.push(Diagnostic::new(Severity::Error, output.trim()));
+    }
+    diagnostics
+}
+
+/// Parse the messages of GNU ld, gold, lld, mold, Apple ld and compiler
+/// drivers.
+///
+/// GNU ld names the function before an undefined reference
+/// ("ld: a.o: in function `_start':"), lld and mold add ">>> referenced by"
+/// lines after a message, and Apple ld lists undefined symbols on indented
+/// lines; these become details of the message they belong to.
+pub fn parse_linker(output: &str) -> Vec<Diagnostic>
+{
+    let mut diagnostics: Vec<Diagnostic> = Vec::new();
+    // Object and function named by the last "in function" line.
+    let mut context: Option<(String, String)> = None;
+
+    for line in output.lines()
+    {
+        let trimmed = line.trim();
+        if trimmed.is_empty()
+        {
+            continue;
+        }
+
+        if line.starts_with(">>>") || line.starts_with(char::is_whitespace)
+        {
+            if let Some(last) = diagnostics.last_mut()
+            {
+                let detail = trimmed.trim_start_matches('>').trim();
+                if let (None, Some(location)) = (&last.file, detail.strip_prefix("referenced by "))
+                {
+                    if parse_location(location).is_some()
+                    {
+                        last.locate(location);
+                    }
+                }
+                last.add_detail(detail);
+                continue;
+            }
+        }
+
+        if let Some((location, severity, message)) = split_severity(line)
+        {
+            let mut diagnostic = Diagnostic::new(severity, message.trim());
+            if !location.is_empty() && !is_tool(location)
+            {
+                diagnostic.locate(location);
+            }
+            diagnostics.push(diagnostic);
+            continue;
+        }
+
+        // GNU ld: "ld: a.o: in function `f':", then "a.s:4: undefined
+        // reference to `x'" or "(.text+0x1): undefined reference to `x'".
+        let text = match line.split_once(": ")
+        {
+            Some((tool, rest)) if is_tool(tool) => rest,
+            _ => line,
+        };
+        if let Some((object, function)) = text.strip_suffix("':").and_then(|t| t.split_once(": in function `"))
+        {
+            context = Some((object.to_string(), function.to_string()));
+            continue;
+        }
+
+        let mut diagnostic = Diagnostic::new(Severity::Error, text.trim());
+        if let Some((location, message)) = text.split_once(": ")
+        {
+            if location.starts_with('(')
+            {
+                diagnostic.message = message.trim().to_string();
+                diagnostic.file = context.as_ref().map(|(object, _)| object.clone());
+            } else if parse_location(location).is_some() {
+                diagnostic.message = message.trim().to_string();
+                diagnostic.locate(location);
+            }
+        }
+        if let Some((_, ref function)) = context
+        {
+            if diagnostic.message.contains("reference to")
+            {
+                diagnostic.add_detail(&format!("in function `{}'", function));
+            }
+        }
+        diagnostics.push(diagnostic);
+    }
+
+    if diagnostics.is_empty() && !output.trim().is_empty()
+    {
+        diagnostics.push(Diagnostic::new(Severity::Error, output.trim()));
+    }
+    diagnostics
+}
+
+/// The messages of a tool that succeeded, as warnings.
+///
+/// Whatever a successful run printed is at most a warning, including text
+/// the parser didn't recognize; notes are dropped.
+pub fn as_warnings(diagnostics: Vec<Diagnostic>) -> Vec<Diagnostic>
+{
+    diagnostics
+        .into_iter()
+        .filter(|diagnostic| diagnostic.severity != Severity::Note)
+        .map(|diagnostic| Diagnostic { severity: Severity::Warning, ..diagnostic })
+        .collect()
+}
+
+/// Remove repeated diagnostics, such as a warning in an included file that
+/// every source including it reports, keeping the first of each.
+pub fn dedup(diagnostics: &mut Vec<Diagnostic>)
+{
+    let mut seen = Vec::new();
+    diagnostics.retain(|diagnostic| {
+        if seen.contains(diagnostic)
+        {
+            false
+        } else {
+            seen.push(diagnostic.clone());
+            true
+        }
+    });
+}
+
+/// Width of the token starting at byte `start` of `line`, for the caret.
+fn token_width(line: &str, start: usize) -> usize
+{
+    line.get(start..)
+        .map(|rest| {
+            rest.chars()
+                .take_while(|c| !c.is_whitespace() && *c != ',')
+                .count()
+        })
+        .unwrap_or(0)
+        .max(1)
+}
+
+/// Render one diagnostic, with the source line when `source` has it.
+fn render_one(diagnostic: &Diagnostic, source: Option<&str>) -> String
+{
+    let mut lines = diagnostic.message.lines();
+    let mut text = format!("{}: {}", diagnostic.severity.as_str(), lines.next().unwrap_or_default());
+
+    let source_line = diagnostic
+        .line
+        .and_then(|line| source.and_then(|s| s.lines().nth(line.saturating_sub(1))));
+    let gutter = " ".repeat(diagnostic.line.map_or(0, |line| line.to_string().len()));
+
+    if let Some(ref file) = diagnostic.file
+    {
+        let location = match (diagnostic.line, diagnostic.column)
+        {
+            (Some(line), Some(column)) => format!("{}:{}:{}", file, line, column),
+            (Some(line), None) => format!("{}:{}", file, line),
+            _ => file.clone(),
+        };
+        text.push_str(&format!("\n{}--> {}", gutter, location));
+    }
+
+    if let (Some(line), Some(source_line)) = (diagnostic.line, source_line)
+    {
+        let source_line = source_line.trim_end();
+        // Without a column, point at the start of the statement.
+        let start = match diagnostic.column
+        {
+            Some(column) => source_line
+                .char_indices()
+                .nth(column.saturating_sub(1))
+                .map_or(source_line.len(), |(i, _)| i),
+            None => source_line.len() - source_line.trim_start().len(),
+        };
+        let width = token_width(source_line, start);
+        let indent: String = source_line[..start].chars().map(|c| if c == '\t' { '\t' } else { ' ' }).collect();
+        text.push_str(&format!(
+            "\n{} |\n{} | {}\n{} | {}{}",
+            gutter,
+            line,
+            source_line,
+            gutter,
+            indent,
+            "^".repeat(width)
+        ));
+    }
+
+    for detail in lines
+    {
+        text.push_str(&format!("\n{} = note: {}", gutter, detail));
+    }
+    text
+}
+
+/// Render diagnostics rustc-style, with a snippet of the source line and a
+/// caret under the reported column:
+///
+/// ```text
+/// error: no such instruction: `bad'
+///   --> src/main.s:23
+///    |
+/// 23 |     bad
+///    |     ^^^
+/// ```
+///
+/// Diagnostics are grouped by file, in the order each file first appears
+/// and by line within a file. Those without a file come last.
+pub fn render(diagnostics: &[Diagnostic]) -> String
+{
+    let mut files: Vec<Option<&str>> = Vec::new();
+    let mut groups: BTreeMap<usize, Vec<&Diagnostic>> = BTreeMap::new();
+    for diagnostic in diagnostics
+    {
+        let file = diagnostic.file.as_deref();
+        let index = match files.iter().position(|f| *f == file)
+        {
+            Some(index) => index,
+            None => {
+                files.push(file);
+                files.len() - 1
+            }
+        };
+        groups.entry(index).or_default().push(diagnostic);
+    }
+
+    let mut order: Vec<usize> = groups.keys().copied().collect();
+    order.sort_by_key(|&index| files[index].is_none());
+
+    let mut blocks = Vec::new();
+    for index in order
+    {
+        let source = files[index].and_then(|file| std::fs::read_to_string(file).ok());
+        let mut group = groups.remove(&index).unwrap_or_default();
+        group.sort_by_key(|diagnostic| diagnostic.line);
+        for diagnostic in group
+        {
+            blocks.push(render_one(diagnostic, source.as_deref()));
+        }
+    }
+    blocks.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn located(file: &str, line: usize, column: Option<usize>, severity: Severity, message: &str) -> Diagnostic {
+        Diagnostic { file: Some(file.to_string()), line: Some(line), column, severity, message: message.to_string() }
+    }
+
+    #[test]
+    fn test_parse_gas_and_llvm_mc() {
+        let gas = "a.s: Assembler messages:\na.s:5: Error: no such instruction: `bad'\na.s:9: Warning: careful\n";
+        assert_eq!(
+            parse_assembler(gas),
+            vec![
+                located("a.s", 5, None, Severity::Error, "no such instruction: `bad'"),
+                located("a.s", 9, None, Severity::Warning, "careful"),
+            ]
+        );
+
+        let llvm_mc = "a.s:5:5: error: invalid instruction mnemonic 'bad'\n    bad\n    ^~~\n";
+        assert_eq!(
+            parse_assembler(llvm_mc),
+            vec![located("a.s", 5, Some(5), Severity::Error, "invalid instruction mnemonic 'bad'")]
+        );
+    }
+
+    #[test]
+    fn test_column_zero_is_no_column() {
+        let diagnostics = parse_assembler("main.s:1:0: error: boom\n");
+        assert_eq!(diagnostics, vec![located("main.s", 1, None, Severity::Error, "boom")]);
+
+        // A column 0 that reaches rendering some other way must not panic.
+        let dir = std::env::temp_dir().join(format!("rasm-column-zero-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("main.s");
+        std::fs::write(&file, "    bad\n").unwrap();
+        let diagnostic = located(file.to_str().unwrap(), 1, Some(0), Severity::Error, "boom");
+        let text = render(&[diagnostic]);
+        assert!(text.ends_with("1 |     bad\n  | ^"), "{}", text);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_parse_nasm_and_yasm() {
+        let nasm = "src/boot.asm:3: error: parser: instruction expected\nnasm: fatal: unable to open input file `x.asm'\n";
+        assert_eq!(
+            parse_assembler(nasm),
+            vec![
+                located("src/boot.asm", 3, None, Severity::Error, "parser: instruction expected"),
+                Diagnostic::new(Severity::Error, "unable to open input file `x.asm'"),
+            ]
+        );
+
+        let yasm = "boot.asm:7: warning: value does not fit in 8 bit field\n";
+        assert_eq!(parse_assembler(yasm), vec![located("boot.asm", 7, None, Severity::Warning, "value does not fit in 8 bit field")]);
+        assert_eq!(parse_assembler("something odd\n"), vec![Diagnostic::new(Severity::Error, "something odd")]);
+    }
+
+    #[test]
+    fn test_parse_gnu_ld() {
+        let output = "ld: warning: cannot find entry symbol nope; defaulting to 0000000000401000\n\
+                      ld: c.o: in function `_start':\n\
+                      (.text+0x1): undefined reference to `foo'\n\
+                      /usr/bin/ld: b.o: in function `_start':\n\
+                      /src/b.s:4: undefined reference to `foo'\n\
+                      ld: cannot find -lnope: No such file or directory\n";
+        let diagnostics = parse_linker(output);
+        assert_eq!(diagnostics.len(), 4);
+        assert_eq!(diagnostics[0].severity, Severity::Warning);
+        assert_eq!(diagnostics[0].file, None);
+        assert_eq!(diagnostics[1].file.as_deref(), Some("c.o"));
+        assert_eq!(diagnostics[1].message, "undefined reference to `foo'\nin function `_start'");
+        assert_eq!(diagnostics[2], located("/src/b.s", 4, None, Severity::Error, "undefined reference to `foo'\nin function `_start'"));
+        assert_eq!(diagnostics[3], Diagnostic::new(Severity::Error, "cannot find -lnope: No such file or directory"));
+    }
+
+    #[test]
+    fn test_parse_lld_and_gold() {
+        let lld = "ld.lld: error: undefined symbol: foo\n>>> referenced by b.s:4\n>>>               b.o:(_start)\n";
+        assert_eq!(
+            parse_linker(lld),
+            vec![located("b.s", 4, None, Severity::Error, "undefined symbol: foo\nreferenced by b.s:4\nb.o:(_start)")]
+        );
+
+        let gold = "c.o(.text+0x1): error: undefined reference to 'foo'\n";
+        let diagnostics = parse_linker(gold);
+        assert_eq!(diagnostics[0].file.as_deref(), Some("c.o"));
+        assert_eq!(diagnostics[0].line, None);
+    }
+
+    #[test]
+    fn test_warnings_of_successful_run() {
+        let output = "a.s: Assembler messages:\na.s:9: Warning: careful\na.s:9: Warning: careful\n";
+        let mut warnings = as_warnings(parse_assembler(output));
+        assert_eq!(warnings.len(), 2);
+        dedup(&mut warnings);
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].message, "careful");
+
+        let unrecognized = as_warnings(parse_linker("something odd\n"));
+        assert_eq!(unrecognized[0].severity, Severity::Warning);
+    }
+
+    #[test]
+    fn test_failures_are_reported_together() {
+        let failed = |file: &str, message: &str| -> anyhow::Error {
+            let diagnostics = parse_assembler(&format!("{}:1: Error: {}\n", file, message));
+            ToolError { tool: "Assembler (gas)".to_string(), diagnostics }.into()
+        };
+        let failures = Failures {
+            errors: vec![failed("a.s", "bad"), failed("b.s", "worse"), anyhow::anyhow!("Failed to read c.s")],
+            total: 4,
+        };
+        let text = failures.to_string();
+        assert_eq!(text.matches("Assembler (gas) failed:").count(), 1);
+        assert!(text.contains("error: bad\n --> a.s:1\nerror: worse\n --> b.s:1\n"), "{}", text);
+        assert!(text.ends_with("Failed to read c.s\n3 of 4 file(s) failed to assemble"), "{}", text);
+    }
+
+    #[test]
+    fn test_render_groups_by_file_with_snippet() {
+        let dir = std::env::temp_dir().join(format!("rasm-diagnostics-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let source = dir.join("main.s");
+        std::fs::write(&source, "_start:\n    bad\n    movl $1, %eax,\n").unwrap();
+        let file = source.to_str().unwrap();
+
+        let diagnostics = vec![
+            located(file, 3, Some(19), Severity::Error, "unknown token in expression"),
+            Diagnostic::new(Severity::Error, "collect2: ld returned 1 exit status"),
+            located(file, 2, None, Severity::Error, "no such instruction: `bad'"),
+        ];
+        let text = render(&diagnostics);
+        let expected = format!(
+            "error: no such instruction: `bad'\n --> {file}:2\n  |\n2 |     bad\n  |     ^^^\n\
+             error: unknown token in expression\n --> {file}:3:19\n  |\n3 |     movl $1, %eax,\n  |                   ^\n\
+             error: collect2: ld returned 1 exit status",
+            file = file
+        );
+        assert_eq!(text, expected);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
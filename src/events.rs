@@ -0,0 +1,360 @@
+use crate::cache::CacheStatus;
+use crate::config::{ArtifactKind, Config};
+use crate::diagnostics::{self, Diagnostic, Failures, Severity, ToolError};
+use crate::report::{self, Step, Summary};
+use anyhow::Result;
+use serde::Serialize;
+use std::io::Write;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// How build progress is reported, chosen with `--message-format`.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MessageFormat
+{
+    /// Text for people, colored when the terminal supports it
+    #[default]
+    Human,
+    /// One JSON event per line on stdout, for editors and CI
+    Json,
+}
+
+/// A step of a build, printed as one line of JSON with `--message-format json`.
+///
+/// The `event` field names the kind of event; the other fields depend on it.
+#[derive(Serialize, Debug)]
+#[serde(tag = "event", rename_all = "kebab-case")]
+pub enum Event<'a>
+{
+    BuildStarted
+    {
+        /// Project directory; paths in the events that follow are relative to it.
+        directory: String,
+        profile: &'a str,
+        target: &'a str,
+    },
+    FileAssembled
+    {
+        source: &'a str,
+        object: &'a str,
+        /// "miss" when the assembler ran, "hit" when restored from the
+        /// cache, "fresh" when the object was already up to date.
+        cache: &'static str,
+        duration_ms: f64,
+    },
+    FileFailed
+    {
+        source: &'a str,
+        duration_ms: f64,
+    },
+    Diagnostic
+    {
+        #[serde(flatten)]
+        diagnostic: &'a Diagnostic,
+        /// The diagnostic as `rasm` prints it, with the source snippet.
+        rendered: String,
+    },
+    LinkFinished
+    {
+        name: &'a str,
+        kind: &'static str,
+        output: &'a str,
+        /// Whether the output was already linked from the same inputs.
+        up_to_date: bool,
+        duration_ms: f64,
+    },
+    LinkFailed
+    {
+        name: &'a str,
+        kind: &'static str,
+        output: &'a str,
+        duration_ms: f64,
+    },
+    BuildFinished
+    {
+        success: bool,
+        duration_ms: f64,
+    },
+}
+
+impl<'a> Event<'a>
+{
+    pub fn file_assembled(source: &'a str, object: &'a str, status: CacheStatus, duration: Duration) -> Event<'a>
+    {
+        let cache = match status
+        {
+            CacheStatus::UpToDate => "fresh",
+            CacheStatus::Hit => "hit",
+            CacheStatus::Miss => "miss",
+        };
+        Event::FileAssembled { source, object, cache, duration_ms: millis(duration) }
+    }
+
+    pub fn file_failed(source: &'a str, duration: Duration) -> Event<'a>
+    {
+        Event::FileFailed { source, duration_ms: millis(duration) }
+    }
+
+    pub fn diagnostic(diagnostic: &'a Diagnostic) -> Event<'a>
+    {
+        Event::Diagnostic { diagnostic, rendered: diagnostics::render(std::slice::from_ref(diagnostic)) }
+    }
+
+    pub fn link_finished(name: &'a str, kind: ArtifactKind, output: &'a str, up_to_date: bool, duration: Duration) -> Event<'a>
+    {
+        Event::LinkFinished { name, kind: kind.key(), output, up_to_date, duration_ms: millis(duration) }
+    }
+
+    pub fn link_failed(name: &'a str, kind: ArtifactKind, output: &'a str, duration: Duration) -> Event<'a>
+    {
+        Event::LinkFailed { name, kind: kind.key(), output, duration_ms: millis(duration) }
+    }
+}
+
+fn millis(duration: Duration) -> f64
+{
+    (duration.as_secs_f64() * 1000.0 * 1000.0).round() / 1000.0
+}
+
+/// Where the events of one build go: printed as JSON lines with
+/// `--message-format json`, and collected into a summary for `--report`.
+///
+/// Safe to share between the parallel assembly workers.
+pub struct Emitter
+{
+    out: Option<Mutex<Box<dyn Write + Send>>>,
+    summary: Option<Mutex<Summary>>,
+}
+
+impl Emitter
+{
+    /// An emitter for the output and reports asked for in `config`.
+    pub fn new(config: &Config) -> Emitter
+    {
+        let out: Option<Box<dyn Write + Send>> =
+            (config.message_format == MessageFormat::Json).then(|| Box::new(std::io::stdout()) as _);
+        Emitter::with_output(out, !config.reports.is_empty() || config.summary_file.is_some())
+    }
+
+    /// An emitter writing JSON lines to `out`, if any, and collecting a
+    /// summary when `summarize` is set.
+    pub fn with_output(out: Option<Box<dyn Write + Send>>, summarize: bool) -> Emitter
+    {
+        Emitter { out: out.map(Mutex::new), summary: summarize.then(|| Mutex::new(Summary::default())) }
+    }
+
+    /// Whether events are printed as JSON lines.
+    pub fn is_json(&self) -> bool
+    {
+        self.out.is_some()
+    }
+
+    /// Print `event` as a line of JSON and add it to the summary, as
+    /// configured.
+    pub fn emit(&self, event: &Event)
+    {
+        if let Some(ref summary) = self.summary
+        {
+            record(&mut summary.lock().unwrap(), event);
+        }
+        let Some(ref out) = self.out else
+        {
+            return;
+        };
+        match serde_json::to_string(event)
+        {
+            // One write per event under the lock, so lines from parallel
+            // workers don't mix.
+            Ok(line) => {
+                let mut out = out.lock().unwrap();
+                let _ = writeln!(out, "{}", line).and_then(|_| out.flush());
+            }
+            Err(e) => log::warn!("Failed to serialize build event: {}", e),
+        }
+    }
+
+    /// The summary collected so far, if one was asked for.
+    fn into_summary(self) -> Option<Summary>
+    {
+        self.summary.map(|summary| summary.into_inner().unwrap())
+    }
+}
+
+/// Add `event` to the summary of the build, for the reports.
+fn record(summary: &mut Summary, event: &Event)
+{
+    let step = |name: &str, duration_ms: f64, failed: bool| Step { name: name.to_string(), duration_ms, failed };
+    match *event
+    {
+        Event::FileAssembled { source, duration_ms, .. } => summary.files.push(step(source, duration_ms, false)),
+        Event::FileFailed { source, duration_ms } => summary.files.push(step(source, duration_ms, true)),
+        Event::LinkFinished { output, duration_ms, .. } => summary.links.push(step(output, duration_ms, false)),
+        Event::LinkFailed { output, duration_ms, .. } => summary.links.push(step(output, duration_ms, true)),
+        Event::Diagnostic { diagnostic, .. } => summary.diagnostics.push(diagnostic.clone()),
+        Event::BuildStarted { .. } => {}
+        Event::BuildFinished { duration_ms, .. } => summary.duration_ms = duration_ms,
+    }
+}
+
+/// The diagnostics carried by a build error: the tool's messages for a
+/// failed assembler or linker, or the error itself otherwise.
+fn error_diagnostics(error: &anyhow::Error) -> Vec<Diagnostic>
+{
+    if let Some(failed) = error.downcast_ref::<ToolError>()
+    {
+        return failed.diagnostics.clone();
+    }
+    if let Some(failures) = error.downcast_ref::<Failures>()
+    {
+        return failures.errors.iter().flat_map(error_diagnostics).collect();
+    }
+    vec![Diagnostic::new(Severity::Error, format!("{:#}", error))]
+}
+
+/// Run `build`, framed by build-started and build-finished events. When it
+/// fails, the diagnostics of its error are emitted before build-finished.
+/// The reports are written once it has finished, whether or not it succeeded.
+pub fn build<T>(config: &Config, build: impl FnOnce(&Emitter) -> Result<T>) -> Result<T>
+{
+    let (result, summary) = run(config, Emitter::new(config), build);
+    let Some(summary) = summary else
+    {
+        return result;
+    };
+    let written = report::write(config, &summary);
+    match (result, written)
+    {
+        (Ok(value), written) => written.map(|_| value),
+        (Err(e), Err(report_error)) => {
+            log::warn!("{:#}", report_error);
+            Err(e)
+        }
+        (Err(e), Ok(())) => Err(e),
+    }
+}
+
+/// Run `build` with `emitter`, returning its result and the summary.
+fn run<T>(config: &Config, emitter: Emitter, build: impl FnOnce(&Emitter) -> Result<T>) -> (Result<T>, Option<Summary>)
+{
+    let started = Instant::now();
+    let directory = std::env::current_dir().map(|dir| dir.display().to_string()).unwrap_or_default();
+    emitter.emit(&Event::BuildStarted { directory, profile: &config.profile, target: &config.target });
+    let result = build(&emitter);
+    if let Err(ref e) = result
+    {
+        for diagnostic in error_diagnostics(e)
+        {
+            emitter.emit(&Event::diagnostic(&diagnostic));
+        }
+    }
+    emitter.emit(&Event::BuildFinished { success: result.is_ok(), duration_ms: millis(started.elapsed()) });
+    (result, emitter.into_summary())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_json() {
+        let event = Event::file_assembled("src/main.s", "main.s.o", CacheStatus::Hit, Duration::from_micros(1500));
+        assert_eq!(
+            serde_json::to_string(&event).unwrap(),
+            r#"{"event":"file-assembled","source":"src/main.s","object":"main.s.o","cache":"hit","duration_ms":1.5}"#
+        );
+
+        let mut warning = Diagnostic::new(Severity::Warning, "careful");
+        warning.file = Some("missing.s".to_string());
+        warning.line = Some(3);
+        let json: serde_json::Value = serde_json::to_value(Event::diagnostic(&warning)).unwrap();
+        assert_eq!(json["event"], "diagnostic");
+        assert_eq!(json["severity"], "warning");
+        assert_eq!(json["file"], "missing.s");
+        assert_eq!(json["line"], 3);
+        assert_eq!(json["column"], serde_json::Value::Null);
+        assert_eq!(json["rendered"], "warning: careful\n --> missing.s:3");
+
+        let failures: anyhow::Error = Failures { errors: vec![anyhow::anyhow!("Failed to read a.s")], total: 2 }.into();
+        let found = error_diagnostics(&failures);
+        assert_eq!(found, vec![Diagnostic::new(Severity::Error, "Failed to read a.s")]);
+    }
+
+    /// A writer the test can read back after the emitter is done with it.
+    #[derive(Clone, Default)]
+    struct Captured(std::sync::Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Captured {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Captured {
+        fn events(&self) -> Vec<serde_json::Value> {
+            let bytes = self.0.lock().unwrap();
+            String::from_utf8_lossy(&bytes).lines().map(|line| serde_json::from_str(line).unwrap()).collect()
+        }
+    }
+
+    fn run_captured<T>(build: impl FnOnce(&Emitter) -> Result<T>) -> (Result<T>, Summary, Vec<serde_json::Value>) {
+        let (config, _) = crate::config::resolve(&[crate::config::Layer::defaults()]);
+        let out = Captured::default();
+        let (result, summary) = run(&config, Emitter::with_output(Some(Box::new(out.clone())), true), build);
+        (result, summary.unwrap(), out.events())
+    }
+
+    #[test]
+    fn test_failed_build_events() {
+        let (result, summary, events) = run_captured(|events| -> Result<()> {
+            events.emit(&Event::file_assembled("ok.s", "ok.s.o", CacheStatus::Miss, Duration::from_millis(2)));
+            events.emit(&Event::file_failed("bad.s", Duration::from_millis(1)));
+            let mut error = Diagnostic::new(Severity::Error, "no such instruction: `bogus'");
+            error.file = Some("bad.s".to_string());
+            error.line = Some(4);
+            let failed = ToolError { tool: "Assembler (gas)".to_string(), diagnostics: vec![error] };
+            Err(Failures { errors: vec![failed.into()], total: 2 }.into())
+        });
+        assert!(result.is_err());
+
+        let kinds: Vec<&str> = events.iter().map(|event| event["event"].as_str().unwrap()).collect();
+        assert_eq!(kinds, ["build-started", "file-assembled", "file-failed", "diagnostic", "build-finished"]);
+        assert_eq!(events[1]["cache"], "miss");
+        assert_eq!(events[2]["source"], "bad.s");
+        assert_eq!(events[3]["severity"], "error");
+        assert_eq!(events[3]["file"], "bad.s");
+        assert_eq!(events[3]["line"], 4);
+        assert_eq!(events[4]["success"], false);
+
+        let failed: Vec<(&str, bool)> = summary.files.iter().map(|step| (step.name.as_str(), step.failed)).collect();
+        assert_eq!(failed, [("ok.s", false), ("bad.s", true)]);
+        assert!(summary.links.is_empty());
+        assert_eq!(summary.diagnostics.len(), 1);
+        assert_eq!(summary.diagnostics[0].message, "no such instruction: `bogus'");
+    }
+
+    #[test]
+    fn test_cached_build_events() {
+        let (result, summary, events) = run_captured(|events| {
+            events.emit(&Event::file_assembled("main.s", "main.s.o", CacheStatus::UpToDate, Duration::ZERO));
+            events.emit(&Event::link_finished("app", ArtifactKind::Bin, "build/app", true, Duration::ZERO));
+            Ok(())
+        });
+        assert!(result.is_ok());
+
+        let kinds: Vec<&str> = events.iter().map(|event| event["event"].as_str().unwrap()).collect();
+        assert_eq!(kinds, ["build-started", "file-assembled", "link-finished", "build-finished"]);
+        assert_eq!(events[1]["cache"], "fresh");
+        assert_eq!(events[2]["up_to_date"], true);
+        assert_eq!(events[2]["kind"], "bin");
+        assert_eq!(events[3]["success"], true);
+
+        assert_eq!(summary.files.len(), 1);
+        assert_eq!(summary.links.len(), 1);
+        assert!(!summary.links[0].failed);
+        assert!(summary.diagnostics.is_empty());
+    }
+}
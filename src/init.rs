@@ -0,0 +1,214 @@
+use crate::config;
+use crate::target::{self, Arch, Os, Target};
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const LINUX_X86_64: &str = r#"# Hello world - x86_64 Linux
+
+    .global _start
+
+    .text
+_start:
+    # write(1, message, length)
+    mov     $1, %rax
+    mov     $1, %rdi
+    lea     message(%rip), %rsi
+    mov     $length, %rdx
+    syscall
+
+    # exit(0)
+    mov     $60, %rax
+    xor     %rdi, %rdi
+    syscall
+
+    .data
+message:
+    .ascii  "Hello, world!\n"
+    .set    length, . - message
+"#;
+
+const LINUX_AARCH64: &str = r#"// Hello world - ARM64 Linux
+
+    .global _start
+
+    .text
+_start:
+    // write(1, message, length)
+    mov     x0, #1
+    adr     x1, message
+    mov     x2, #length
+    mov     x8, #64
+    svc     #0
+
+    // exit(0)
+    mov     x0, #0
+    mov     x8, #93
+    svc     #0
+
+    .data
+message:
+    .ascii  "Hello, world!\n"
+    .set    length, . - message
+"#;
+
+const MACOS_AARCH64: &str = r#"// Hello world - ARM64 macOS
+
+.global _start
+.align 2
+
+_start:
+    // write(1, message, length)
+    mov     x0, #1
+    adrp    x1, message@PAGE
+    add     x1, x1, message@PAGEOFF
+    mov     x2, #14
+    mov     x16, #4
+    svc     #0x80
+
+    // exit(0)
+    mov     x0, #0
+    mov     x16, #1
+    svc     #0x80
+
+.data
+message:
+    .ascii  "Hello, world!\n"
+"#;
+
+const MACOS_X86_64: &str = r#"# Hello world - x86_64 macOS
+
+    .global _start
+
+    .text
+_start:
+    # write(1, message, length)
+    mov     $0x2000004, %rax
+    mov     $1, %rdi
+    lea     message(%rip), %rsi
+    mov     $14, %rdx
+    syscall
+
+    # exit(0)
+    mov     $0x2000001, %rax
+    xor     %rdi, %rdi
+    syscall
+
+    .data
+message:
+    .ascii  "Hello, world!\n"
+"#;
+
+/// Starter program for `target`, or a stub for targets without one.
+fn template(target: &Target) -> String
+{
+    match (target.arch, target.os)
+    {
+        (Arch::X86_64, Os::Linux) => LINUX_X86_64.to_string(),
+        (Arch::Aarch64, Os::Linux) => LINUX_AARCH64.to_string(),
+        (Arch::Aarch64, Os::Macos) => MACOS_AARCH64.to_string(),
+        (Arch::X86_64, Os::Macos) => MACOS_X86_64.to_string(),
+        _ => format!(
+            "# Entry point for {}\n\n    .global {}\n\n    .text\n{}:\n",
+            target.triple, target.entry, target.entry
+        ),
+    }
+}
+
+/// Contents of the generated `.rasm.toml`.
+fn manifest(name: &str, target: Option<&Target>) -> String
+{
+    let mut text = format!(
+        "input_files = [\"src/*.s\"]\noutput_file = {}\n",
+        toml::Value::String(name.to_string())
+    );
+    if let Some(target) = target
+    {
+        text.push_str(&format!("target = \"{}\"\n", target.triple));
+    }
+    text
+}
+
+/// Create a new project in `dir`: a `.rasm.toml` and a `src/main.s`
+/// hello world for the target, so that `rasm build` works right away.
+///
+/// # Arguments
+/// * `dir` - Project directory, created if missing
+/// * `name` - Output binary name; defaults to the directory name
+/// * `target` - Target triple written to the config; defaults to the host
+///
+/// # Returns
+/// The files that were created
+pub fn init(dir: &Path, name: Option<&str>, target: Option<&str>) -> Result<Vec<PathBuf>>
+{
+    if let Some(existing) = find_existing_config(dir)
+    {
+        return Err(anyhow::anyhow!(
+            "{} already exists; refusing to overwrite an existing project",
+            existing.display()
+        ));
+    }
+
+    let explicit_target = target.map(target::parse).transpose()?;
+    let target = explicit_target.unwrap_or_else(target::host);
+
+    let name = match name
+    {
+        Some(name) => name.to_string(),
+        None => fs::create_dir_all(dir)
+            .and_then(|_| dir.canonicalize())
+            .ok()
+            .and_then(|path| path.file_name().map(|n| n.to_string_lossy().into_owned()))
+            .unwrap_or_else(|| "app".to_string()),
+    };
+
+    let manifest_path = dir.join(".rasm.toml");
+    let source_path = dir.join("src").join("main.s");
+    fs::create_dir_all(dir.join("src"))
+        .with_context(|| format!("Failed to create {}", dir.join("src").display()))?;
+
+    let mut created = vec![manifest_path.clone()];
+    fs::write(&manifest_path, manifest(&name, explicit_target))
+        .with_context(|| format!("Failed to write {}", manifest_path.display()))?;
+    // Keep sources that are already there.
+    if !source_path.exists()
+    {
+        fs::write(&source_path, template(target))
+            .with_context(|| format!("Failed to write {}", source_path.display()))?;
+        created.push(source_path);
+    }
+
+    Ok(created)
+}
+
+/// Return the first configuration file `dir` already has.
+fn find_existing_config(dir: &Path) -> Option<PathBuf>
+{
+    config::CONFIG_FILE_NAMES
+        .iter()
+        .map(|name| dir.join(name))
+        .find(|path| path.exists())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_init_creates_buildable_project() {
+        let dir = std::env::temp_dir().join(format!("rasm-init-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+
+        let created = init(&dir, Some("hello"), Some("aarch64-unknown-linux-gnu")).unwrap();
+        assert_eq!(created, vec![dir.join(".rasm.toml"), dir.join("src/main.s")]);
+
+        let file = config::load_config(dir.join(".rasm.toml").to_str().unwrap()).unwrap();
+        assert_eq!(file.input_files, Some(vec!["src/*.s".to_string()]));
+        assert_eq!(file.output_file.as_deref(), Some("hello"));
+        assert_eq!(file.target.as_deref(), Some("aarch64-unknown-linux-gnu"));
+        assert!(fs::read_to_string(dir.join("src/main.s")).unwrap().contains("svc     #0"));
+
+        assert!(init(&dir, None, None).is_err());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -1,58 +1,523 @@
-use crate::cli::Config;
-use anyhow::Result;
+use crate::cache::{BuildCache, KeyHasher};
+use crate::config::{Artifact, ArtifactKind, Config};
+use crate::diagnostics::{self, Diagnostic, ToolError};
+use anyhow::{Context, Result};
 use log::{debug, info};
+use crate::target::{self, Arch, ObjectFormat, Os, Target};
+use std::path::{Path, PathBuf};
 use std::process::Command;
 
-/// Link object files into a final executable.
+/// A linker flavor and how to invoke it.
+///
+/// Each implementation translates the target and output into the flags its
+/// command line expects; `extra_flags` are appended after `driver_flags`.
+pub trait Linker: Sync 
+{
+    /// Short name of the linker flavor, used in logs and error messages.
+    fn name(&self) -> &'static str;
+
+    /// Flags that strip symbols from the output.
+    fn strip_flags(&self, target: &Target) -> Vec<String>;
+
+    /// Flags that make the output a shared library recorded as `soname`,
+    /// exporting only the symbols in `exports` when given (a version script
+    /// for ELF, a symbol list for Mach-O; see `exports_file`).
+    fn shared_flags(&self, target: &Target, soname: &str, exports: Option<&Path>) -> Vec<String>;
+
+    /// Build the command that links `object_files` into `output`, starting
+    /// execution at `entry`. Shared libraries have no entry point.
+    fn command(&self, program: &str, object_files: &[String], output: &str, target: &Target, entry: Option<&str>) -> Command;
+
+    /// Translate flags written for a compiler driver, as `LDFLAGS` usually
+    /// are, for this linker: `-Wl,a,b` becomes `a b` and `-Xlinker a`
+    /// becomes `a`. Other flags are kept as they are.
+    fn driver_flags(&self, flags: &[String]) -> Vec<String> 
+    {
+        let mut translated = Vec::new();
+        let mut flags = flags.iter();
+        while let Some(flag) = flags.next() 
+        {
+            if let Some(options) = flag.strip_prefix("-Wl,") 
+            {
+                translated.extend(options.split(',').filter(|option| !option.is_empty()).map(str::to_string));
+            } else if flag == "-Xlinker" {
+                translated.extend(flags.next().cloned());
+            } else {
+                translated.push(flag.clone());
+            }
+        }
+        translated
+    }
+}
+
+/// GNU-style ELF linkers: ld (bfd), gold, ld.lld and mold.
+pub struct GnuLd;
+
+/// Mach-O linkers: Apple ld and ld64.lld.
+pub struct Ld64;
+
+/// A C compiler driver (cc, gcc, clang) that links against libc.
+pub struct CcDriver;
+
+impl Linker for GnuLd 
+{
+    fn name(&self) -> &'static str 
+    {
+        "ld"
+    }
+
+    fn strip_flags(&self, _target: &Target) -> Vec<String> 
+    {
+        vec!["-s".to_string()]
+    }
+
+    fn shared_flags(&self, _target: &Target, soname: &str, exports: Option<&Path>) -> Vec<String> 
+    {
+        let mut flags = vec!["-shared".to_string(), "-soname".to_string(), soname.to_string()];
+        if let Some(exports) = exports 
+        {
+            flags.push(format!("--version-script={}", exports.display()));
+        }
+        flags
+    }
+
+    fn command(&self, program: &str, object_files: &[String], output: &str, target: &Target, entry: Option<&str>) -> Command 
+    {
+        let mut cmd = Command::new(program);
+        if let Some(emulation) = gnu_emulation(target) 
+        {
+            cmd.args(["-m", emulation]);
+        }
+        if let Some(entry) = entry 
+        {
+            cmd.args(["-e", entry]);
+        }
+        cmd.args(object_files).arg("-o").arg(output);
+        cmd
+    }
+}
+
+/// Emulation passed to GNU-style linkers with `-m`, so that a host `ld`
+/// links for the target instead of its own default, such as i386 objects
+/// on an x86-64 host.
+pub fn gnu_emulation(target: &Target) -> Option<&'static str> 
+{
+    match (target.format, target.arch) 
+    {
+        (ObjectFormat::Elf, Arch::X86_64) => Some("elf_x86_64"),
+        (ObjectFormat::Elf, Arch::X86) => Some("elf_i386"),
+        (ObjectFormat::Elf, Arch::Aarch64) if target.os == Os::Bare => Some("aarch64elf"),
+        (ObjectFormat::Elf, Arch::Aarch64) => Some("aarch64linux"),
+        (ObjectFormat::Elf, Arch::Riscv64) => Some("elf64lriscv"),
+        (ObjectFormat::Coff, Arch::X86_64) => Some("i386pep"),
+        (ObjectFormat::Coff, Arch::X86) => Some("i386pe"),
+        _ => None,
+    }
+}
+
+impl Linker for Ld64 
+{
+    fn name(&self) -> &'static str 
+    {
+        "ld64"
+    }
+
+    fn strip_flags(&self, _target: &Target) -> Vec<String> 
+    {
+        // Apple ld ignores -s; drop debug info and local symbols instead.
+        vec!["-S".to_string(), "-x".to_string()]
+    }
+
+    fn shared_flags(&self, _target: &Target, soname: &str, exports: Option<&Path>) -> Vec<String> 
+    {
+        let mut flags = vec!["-dylib".to_string(), "-install_name".to_string(), soname.to_string()];
+        if let Some(exports) = exports 
+        {
+            flags.push("-exported_symbols_list".to_string());
+            flags.push(exports.display().to_string());
+        }
+        flags
+    }
+
+    fn command(&self, program: &str, object_files: &[String], output: &str, target: &Target, entry: Option<&str>) -> Command 
+    {
+        let mut cmd = Command::new(program);
+        cmd.args(["-arch", darwin_arch(target)]);
+        if let Some(entry) = entry 
+        {
+            cmd.args(["-e", entry]);
+        }
+        cmd.arg("-lSystem");
+        if let Some(sdk_path) = get_sdk_path() 
+        {
+            cmd.arg("-syslibroot").arg(sdk_path);
+        }
+        // Apple ld infers the platform, ld64.lld requires it.
+        if program.contains("lld") 
+        {
+            cmd.args(["-platform_version", "macos", "11.0", "0"]);
+        }
+        cmd.args(object_files).arg("-o").arg(output);
+        cmd
+    }
+}
+
+impl Linker for CcDriver 
+{
+    fn name(&self) -> &'static str 
+    {
+        "cc"
+    }
+
+    fn strip_flags(&self, target: &Target) -> Vec<String> 
+    {
+        if target.os == Os::Macos 
+        {
+            vec!["-Wl,-S,-x".to_string()]
+        } else {
+            vec!["-s".to_string()]
+        }
+    }
+
+    fn shared_flags(&self, target: &Target, soname: &str, exports: Option<&Path>) -> Vec<String> 
+    {
+        let mut flags = Vec::new();
+        if target.os == Os::Macos 
+        {
+            flags.push("-dynamiclib".to_string());
+            flags.push(format!("-Wl,-install_name,{}", soname));
+            if let Some(exports) = exports 
+            {
+                flags.push(format!("-Wl,-exported_symbols_list,{}", exports.display()));
+            }
+        } else {
+            flags.push("-shared".to_string());
+            flags.push(format!("-Wl,-soname,{}", soname));
+            if let Some(exports) = exports 
+            {
+                flags.push(format!("-Wl,--version-script={}", exports.display()));
+            }
+        }
+        flags
+    }
+
+    fn command(&self, program: &str, object_files: &[String], output: &str, target: &Target, entry: Option<&str>) -> Command 
+    {
+        let mut cmd = Command::new(program);
+        if target.os == Os::Macos 
+        {
+            cmd.arg("-lSystem");
+            let sdk_path = get_sdk_path().unwrap_or_else(|| String::from("/"));
+            cmd.arg(format!("-Wl,-syslibroot,{}", sdk_path));
+            cmd.args(["-arch", darwin_arch(target)]);
+        }
+        if let Some(entry) = entry 
+        {
+            cmd.args(["-e", entry]);
+        }
+        cmd.args(object_files).arg("-o").arg(output);
+        cmd
+    }
+
+    fn driver_flags(&self, flags: &[String]) -> Vec<String> 
+    {
+        flags.to_vec()
+    }
+}
+
+/// Architecture name as spelled by Apple's tools.
+fn darwin_arch(target: &Target) -> &'static str 
+{
+    match target.arch 
+    {
+        Arch::Aarch64 => "arm64",
+        Arch::X86 => "i386",
+        arch => arch.triple_name(),
+    }
+}
+
+/// Resolve the `linker` setting to the program to run.
+///
+/// Short names are expanded ("gold" -> "ld.gold", "lld" -> "ld.lld" or
+/// "ld64.lld"); anything else is used as given. Without a setting, the
+/// target's default linker is used.
+pub fn linker_program(config: &Config, target: &Target) -> String 
+{
+    let macho = target.format == ObjectFormat::MachO;
+    match config.linker.as_deref() 
+    {
+        None => target.linker.to_string(),
+        Some("gold") => "ld.gold".to_string(),
+        Some("lld") if macho => "ld64.lld".to_string(),
+        Some("lld") => "ld.lld".to_string(),
+        Some(program) => program.to_string(),
+    }
+}
+
+/// Pick the linker flavor for a program from its file name.
+/// Plain `ld` is Apple's linker for Mach-O targets and a GNU-style linker otherwise.
+pub fn flavor_for(program: &str, target: &Target) -> &'static dyn Linker 
+{
+    let name = Path::new(program)
+        .file_name()
+        .map(|n| n.to_string_lossy().to_ascii_lowercase())
+        .unwrap_or_default();
+    let name = name.strip_suffix(".exe").unwrap_or(&name);
+
+    if name.ends_with("cc") || name.ends_with("gcc") || name.starts_with("clang") || name.ends_with("c++") 
+    {
+        &CcDriver
+    } else if name.starts_with("ld64") || (name == "ld" && target.format == ObjectFormat::MachO) {
+        &Ld64
+    } else {
+        &GnuLd
+    }
+}
+
+/// Link object files into an executable or shared library, or archive them
+/// into a static library.
 /// 
-/// Uses platform-specific linkers:
-/// - macOS: clang with system libraries and SDK
-/// - Other: ld
+/// The linker comes from the `linker` setting or the target's default (see
+/// `linker_program`), and its flavor decides how the target and entry point
+/// are passed.
 /// 
 /// # Arguments
+/// * `artifact` - Artifact to build, with its kind, output path and link flags
 /// * `object_files` - List of object file paths to link
-/// * `config` - Configuration containing linker settings and output path
-pub fn link(object_files: &[String], config: &Config) -> Result<()> 
+/// * `config` - Configuration containing linker settings
+/// 
+/// # Returns
+/// The warnings the linker or archiver printed
+pub fn link(artifact: &Artifact, object_files: &[String], config: &Config) -> Result<Vec<Diagnostic>> 
 {
-    let mut cmd = if cfg!(target_os = "macos") 
+    if artifact.kind == ArtifactKind::StaticLib 
     {
-        let mut c = Command::new("clang");
-        c.args(&["-lSystem"]);
-        let sdk_path = get_sdk_path().unwrap_or_else(|| String::from("/"));
-        c.arg(format!("-Wl,-syslibroot,{}", sdk_path));
-        c.args(&["-e", "_start", "-arch", &config.target]);
-        c
-    } else {
-        Command::new("ld")
+        return archive(object_files, &artifact.output_file, config);
+    }
+
+    let target = target::parse(&config.target)?;
+    let program = linker_program(config, target);
+    let flavor = flavor_for(&program, target);
+
+    let entry = match artifact.kind 
+    {
+        ArtifactKind::Bin => Some(artifact.entry.as_deref().unwrap_or(target.entry)),
+        _ => None,
     };
+    let mut cmd = flavor.command(&program, object_files, &artifact.output_file, target, entry);
+    if artifact.kind == ArtifactKind::SharedLib 
+    {
+        let exports = match artifact.exported_symbols 
+        {
+            Some(ref symbols) => Some(write_exports_file(artifact, symbols, target, config)?),
+            None => None,
+        };
+        cmd.args(flavor.shared_flags(target, &soname(artifact, target), exports.as_deref()));
+    }
+    if config.strip 
+    {
+        cmd.args(flavor.strip_flags(target));
+    }
+    cmd.args(flavor.driver_flags(&config.extra_flags)).args(&artifact.extra_flags);
 
-    // Append all object files.
+    if config.verbose || config.dry_run 
+    {
+        info!("Linking into output: {} (using {})", artifact.output_file, program);
+        debug!("Linker command: {:?}", cmd);
+    }
+    run(cmd, "Linker", &program, flavor.name(), config)
+}
+
+/// Name a shared library is recorded under: the `soname` setting, or its
+/// file name, found through the runtime search path on macOS.
+pub fn soname(artifact: &Artifact, target: &Target) -> String 
+{
+    if let Some(ref soname) = artifact.soname 
+    {
+        return soname.clone();
+    }
+    let file_name = Path::new(&artifact.output_file)
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    if target.format == ObjectFormat::MachO 
+    {
+        format!("@rpath/{}", file_name)
+    } else {
+        file_name
+    }
+}
+
+/// Contents of the file that limits a shared library's exports to `symbols`:
+/// a version script that hides every other symbol for ELF, and a plain list
+/// for Mach-O's `-exported_symbols_list`.
+pub fn exports_file(symbols: &[String], target: &Target) -> String 
+{
+    if target.format == ObjectFormat::MachO 
+    {
+        return symbols.iter().map(|symbol| format!("{}\n", symbol)).collect();
+    }
+    let mut script = String::from("{\n  global:\n");
+    for symbol in symbols 
+    {
+        script.push_str(&format!("    {};\n", symbol));
+    }
+    script.push_str("  local: *;\n};\n");
+    script
+}
+
+/// Write the exports file of `artifact` to the build directory.
+///
+/// # Returns
+/// Path of the file; on a dry run it is not written
+fn write_exports_file(artifact: &Artifact, symbols: &[String], target: &Target, config: &Config) -> Result<PathBuf> 
+{
+    let file_name = Path::new(&artifact.output_file)
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let path = Path::new(&config.build_dir()).join(format!("{}.exports", file_name));
+    if !config.dry_run 
+    {
+        std::fs::create_dir_all(config.build_dir())?;
+        std::fs::write(&path, exports_file(symbols, target))
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+    }
+    Ok(path)
+}
+
+/// Resolve the `archiver` setting to the program to run.
+pub fn archiver_program(config: &Config) -> String 
+{
+    config.archiver.clone().unwrap_or_else(|| "ar".to_string())
+}
+
+/// Build the command that archives `object_files` into `output`.
+///
+/// Members are appended in the order given rather than replaced by name
+/// (`q`, not `r`), so objects with the same file name from different
+/// directories are all kept. The archive is deterministic: `D` zeroes
+/// timestamps, owners and modes in GNU ar and llvm-ar, and Apple's ar, which
+/// has no `D`, reads `ZERO_AR_DATE` instead.
+pub fn archive_command(program: &str, object_files: &[String], output: &str) -> Command 
+{
+    let name = Path::new(program)
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let apple_ar = cfg!(target_os = "macos") && name == "ar";
+
+    let mut cmd = Command::new(program);
+    cmd.arg(if apple_ar { "qcs" } else { "qcsD" })
+        .arg(output)
+        .args(object_files)
+        .env("ZERO_AR_DATE", "1");
+    cmd
+}
+
+/// Archive object files into a static library.
+///
+/// The archive is rebuilt from scratch, so objects removed from the
+/// artifact's sources don't linger in it, and each object is added once,
+/// in the order of the artifact's sources.
+pub fn archive(object_files: &[String], output: &str, config: &Config) -> Result<Vec<Diagnostic>> 
+{
+    let program = archiver_program(config);
+    let mut members: Vec<String> = Vec::new();
     for obj in object_files 
     {
-        cmd.arg(obj);
+        if !members.contains(obj) 
+        {
+            members.push(obj.clone());
+        }
     }
-    cmd.arg("-o")
-       .arg(&config.output_file);
-    cmd.args(&config.extra_flags);
+    let cmd = archive_command(&program, &members, output);
 
     if config.verbose || config.dry_run 
     {
-        info!("Linking into output: {}", config.output_file);
-        debug!("Linker command: {:?}", cmd);
+        info!("Archiving into output: {} (using {})", output, program);
+        debug!("Archiver command: {:?}", cmd);
     }
+    if !config.dry_run 
+    {
+        match std::fs::remove_file(output) 
+        {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                return Err(anyhow::anyhow!("Failed to remove old archive {}: {}", output, e));
+            }
+            _ => {}
+        }
+    }
+    run(cmd, "Archiver", &program, "ar", config)
+}
+
+/// Run a link or archive command, unless this is a dry run.
+///
+/// # Arguments
+/// * `tool` - "Linker" or "Archiver", for error messages
+/// * `program` - Program the command runs
+/// * `flavor` - Short name of the tool flavor
+/// 
+/// # Returns
+/// The warnings the tool printed when it succeeded
+fn run(mut cmd: Command, tool: &str, program: &str, flavor: &str, config: &Config) -> Result<Vec<Diagnostic>> 
+{
     if config.dry_run 
     {
-        return Ok(());
+        return Ok(Vec::new());
     }
-    let output = cmd.output()?;
+    let output = cmd
+        .output()
+        .with_context(|| format!("Failed to run {} '{}'", tool.to_lowercase(), program))?;
+    let messages = diagnostics::parse_linker(&String::from_utf8_lossy(&output.stderr));
     if !output.status.success() 
     {
-        return Err(anyhow::anyhow!(
-            "Linker failed:\n{}",
-            String::from_utf8_lossy(&output.stderr)
-        ));
+        return Err(ToolError 
+        {
+            tool: format!("{} ({})", tool, flavor),
+            diagnostics: messages,
+        }
+        .into());
     }
-    Ok(())
+    Ok(diagnostics::as_warnings(messages))
+}
+
+/// Compute the cache key for linking `object_files` into `artifact` with the
+/// current configuration.
+///
+/// Objects are identified by the keys they were assembled from, so a relink
+/// only happens when an object's inputs or the link settings change.
+pub fn cache_key(artifact: &Artifact, object_files: &[String], config: &Config, cache: &BuildCache) -> String 
+{
+    let tool = match artifact.kind 
+    {
+        ArtifactKind::StaticLib => archiver_program(config),
+        _ => target::parse(&config.target)
+            .map(|target| linker_program(config, target))
+            .unwrap_or_default(),
+    };
+    let mut hasher = KeyHasher::new();
+    hasher
+        .field(&artifact.output_file)
+        .field(artifact.kind.key())
+        .field(artifact.entry.as_deref().unwrap_or_default())
+        .field(&config.target)
+        .field(cache.tool_identity(&tool))
+        .field([config.strip as u8]);
+    for obj in object_files 
+    {
+        hasher.field(obj).field(cache.object_key(obj).unwrap_or_default());
+    }
+    for flag in config.extra_flags.iter().chain(&artifact.extra_flags) 
+    {
+        hasher.field(flag);
+    }
+    hasher.field(artifact.soname.as_deref().unwrap_or_default());
+    for symbol in artifact.exported_symbols.iter().flatten() 
+    {
+        hasher.field(symbol);
+    }
+    hasher.finish()
 }
 
 /// Get the macOS SDK path using xcrun (macOS only).
@@ -80,3 +545,106 @@ fn get_sdk_path() -> Option<String>
     None
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(cmd: &Command) -> Vec<String> {
+        cmd.get_args().map(|a| a.to_string_lossy().to_string()).collect()
+    }
+
+    #[test]
+    fn test_flavor_detection() {
+        let linux = target::parse("x86_64-unknown-linux-gnu").unwrap();
+        let macos = target::parse("aarch64-apple-darwin").unwrap();
+        assert_eq!(flavor_for("ld.lld", linux).name(), "ld");
+        assert_eq!(flavor_for("mold", linux).name(), "ld");
+        assert_eq!(flavor_for("/usr/bin/ld.gold", linux).name(), "ld");
+        assert_eq!(flavor_for("ld64.lld", linux).name(), "ld64");
+        assert_eq!(flavor_for("ld", macos).name(), "ld64");
+        assert_eq!(flavor_for("ld", linux).name(), "ld");
+        assert_eq!(flavor_for("cc", linux).name(), "cc");
+        assert_eq!(flavor_for("clang-17", macos).name(), "cc");
+        assert_eq!(flavor_for("aarch64-linux-gnu-gcc", linux).name(), "cc");
+    }
+
+    #[test]
+    fn test_gnu_ld_command() {
+        let target = target::parse("x86_64-linux").unwrap();
+        let objects = vec!["a.o".to_string(), "b.o".to_string()];
+        let cmd = GnuLd.command("mold", &objects, "app", target, Some("main"));
+        assert_eq!(cmd.get_program(), "mold");
+        assert_eq!(args(&cmd), vec!["-m", "elf_x86_64", "-e", "main", "a.o", "b.o", "-o", "app"]);
+
+        let cmd = GnuLd.command("ld", &objects, "libapp.so", target, None);
+        assert_eq!(args(&cmd), vec!["-m", "elf_x86_64", "a.o", "b.o", "-o", "libapp.so"]);
+    }
+
+    #[test]
+    fn test_gnu_ld_command_selects_emulation() {
+        let objects = vec!["a.o".to_string()];
+        let i686 = target::parse("i686-linux").unwrap();
+        let cmd = GnuLd.command("ld", &objects, "app", i686, Some(i686.entry));
+        assert_eq!(args(&cmd), vec!["-m", "elf_i386", "-e", "_start", "a.o", "-o", "app"]);
+
+        let emulation = |triple: &str| gnu_emulation(target::parse(triple).unwrap());
+        assert_eq!(emulation("aarch64-linux"), Some("aarch64linux"));
+        assert_eq!(emulation("aarch64-unknown-none-elf"), Some("aarch64elf"));
+        assert_eq!(emulation("riscv64-linux"), Some("elf64lriscv"));
+        assert_eq!(emulation("x86_64-pc-windows-gnu"), Some("i386pep"));
+        assert_eq!(emulation("aarch64-macos"), None);
+    }
+
+    #[test]
+    fn test_driver_flags_for_bare_linkers() {
+        let ldflags: Vec<String> = ["-Wl,-O1,--as-needed", "-Xlinker", "-z", "-Xlinker", "noexecstack", "-L/opt/lib"]
+            .iter()
+            .map(|flag| flag.to_string())
+            .collect();
+        let unwrapped = vec!["-O1", "--as-needed", "-z", "noexecstack", "-L/opt/lib"];
+        assert_eq!(GnuLd.driver_flags(&ldflags), unwrapped);
+        assert_eq!(Ld64.driver_flags(&ldflags), unwrapped);
+        assert_eq!(CcDriver.driver_flags(&ldflags), ldflags);
+    }
+
+    #[test]
+    fn test_shared_library_flags() {
+        let linux = target::parse("x86_64-linux").unwrap();
+        let macos = target::parse("aarch64-macos").unwrap();
+        let exports = Path::new("target/rasm/dev/libstrings.so.exports");
+        assert_eq!(
+            GnuLd.shared_flags(linux, "libstrings.so", Some(exports)),
+            vec!["-shared", "-soname", "libstrings.so", "--version-script=target/rasm/dev/libstrings.so.exports"]
+        );
+        assert_eq!(CcDriver.shared_flags(linux, "libstrings.so", None), vec!["-shared", "-Wl,-soname,libstrings.so"]);
+        assert_eq!(
+            Ld64.shared_flags(macos, "@rpath/libstrings.dylib", None),
+            vec!["-dylib", "-install_name", "@rpath/libstrings.dylib"]
+        );
+
+        let symbols = vec!["str_len".to_string(), "str_copy".to_string()];
+        assert_eq!(
+            exports_file(&symbols, linux),
+            "{\n  global:\n    str_len;\n    str_copy;\n  local: *;\n};\n"
+        );
+        assert_eq!(exports_file(&symbols, macos), "str_len\nstr_copy\n");
+    }
+
+    #[test]
+    fn test_archive_command_is_deterministic() {
+        let objects = vec!["a/util.s.o".to_string(), "b/util.s.o".to_string()];
+        let cmd = archive_command("llvm-ar", &objects, "libutil.a");
+        assert_eq!(args(&cmd), vec!["qcsD", "libutil.a", "a/util.s.o", "b/util.s.o"]);
+        assert!(cmd.get_envs().any(|(k, v)| k == "ZERO_AR_DATE" && v == Some("1".as_ref())));
+    }
+
+    #[test]
+    fn test_ld64_command_translates_arch() {
+        let target = target::parse("aarch64-macos").unwrap();
+        let cmd = Ld64.command("ld64.lld", &["a.o".to_string()], "app", target, Some(target.entry));
+        let args = args(&cmd);
+        assert_eq!(&args[..5], ["-arch", "arm64", "-e", "_start", "-lSystem"]);
+        assert!(args.contains(&"-platform_version".to_string()));
+    }
+}
@@ -1,91 +1,263 @@
 mod assembler;
+mod cache;
 mod cli;
 mod config;
+mod deps;
+mod diagnostics;
+mod events;
+mod init;
 mod linker;
+mod report;
+mod schema;
+mod target;
+mod toolchain;
+mod workspace;
 
 use anyhow::Result;
+use cache::CacheStatus;
 use clap::CommandFactory;
 use cli::parse_args;
-use env_logger;
+use diagnostics::Diagnostic;
+use events::{Emitter, Event, MessageFormat};
 use indicatif::{ProgressBar, ProgressStyle};
 use log::{debug, info};
 use notify_debouncer_full::{new_debouncer, notify::RecursiveMode};
 use owo_colors::OwoColorize;
 use rayon::prelude::*;
+use std::collections::HashSet;
 use std::fs;
-use std::path::Path;
-use std::sync::mpsc;
-use std::time::Duration;
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::{Duration, Instant};
 
 fn main() -> Result<()> 
 {
     // Initialize logging (set RUST_LOG to control verbosity, e.g., RUST_LOG=info).
     env_logger::init();
 
-    let mut config = parse_args();
+    let cli = parse_args();
 
-    // Handle shell completions generation
-    if let Some(shell) = config.completions {
-        let mut cmd = cli::Config::command();
-        clap_complete::generate(
-            shell,
-            &mut cmd,
-            "rasm",
-            &mut std::io::stdout(),
-        );
+    if let Some(cli::PrintRequest::TargetList) = cli.print {
+        print!("{}", target::target_list());
+        return Ok(());
+    }
+
+    let Some(command) = cli.command.clone() else {
+        cli::Cli::command().print_help()?;
+        return Ok(());
+    };
+
+    match command {
+        // Handle shell completions generation
+        cli::Command::Completions { shell } => {
+            let mut cmd = cli::Cli::command();
+            clap_complete::generate(
+                shell,
+                &mut cmd,
+                "rasm",
+                &mut std::io::stdout(),
+            );
+            return Ok(());
+        }
+        cli::Command::Config(cli::ConfigCommand::Schema) => {
+            println!("{}", serde_json::to_string_pretty(&schema::json_schema())?);
+            return Ok(());
+        }
+        cli::Command::Init(ref args) => {
+            for path in init::init(&args.path, args.name.as_deref(), cli.options.target.as_deref())? {
+                println!("✓ Created {}", path.display());
+            }
+            return Ok(());
+        }
+        _ => {}
+    }
+
+    // Layer the configuration: built-in defaults < system file < user file
+    // < AS/LD variables < project file < RASM_* variables < command line.
+    let env = |name: &str| std::env::var(name).ok();
+    let mut layers = vec![config::Layer::defaults()];
+    layers.extend(config::shared_layers()?);
+    layers.extend(config::standard_env_layers(&env)?);
+
+    // Paths from the environment and command line are relative to where rasm runs.
+    let mut invocation_layers = config::rasm_env_layers(&env)?;
+    invocation_layers.push(config::Layer { source: config::Source::Cli, values: cli.layer() });
+    let invocation_dir = std::env::current_dir()?;
+
+    let mut config_file = cli.options.config_file.clone().or_else(config::auto_detect_config);
+    let members = command.members().cloned().unwrap_or_default();
+
+    // `--workspace` and `-p` inside a member run for the enclosing workspace.
+    if members.is_set() && cli.options.config_file.is_none() {
+        if let Some(path) = config_file.take() {
+            config_file = Some(workspace::find_root(&path)?.unwrap_or(path));
+        }
+    }
+
+    let mut project = config::FileConfig::default();
+    if let Some(ref path) = config_file {
+        project = config::load_config(path)?;
+        layers.push(config::Layer {
+            source: config::Source::File(path.clone()),
+            values: project.clone(),
+        });
+
+        // Work from the project root, so that paths in the config file and the
+        // build state resolve the same from any subdirectory.
+        let root = config::project_root(path).canonicalize()?;
+        let cwd = invocation_dir.canonicalize()?;
+        if root != cwd {
+            let base = config::project_base(&root, &cwd);
+            for layer in &mut invocation_layers {
+                layer.values.rebase(&base);
+            }
+            std::env::set_current_dir(&root)?;
+            debug!("Changed directory to project root {}", root.display());
+        }
+    }
+    layers.extend(invocation_layers);
+
+    let (mut config, mut origins) = config::resolve(&layers);
+    config.config_file = config_file;
+    config.profile = cli.profile().to_string();
+    config.force = command.force();
+    config.keep_going = command.keep_going();
+    config.message_format = cli.options.message_format.unwrap_or_default();
+    config.reports = cli.options.reports.iter().map(|report| report.relative_to(&invocation_dir)).collect();
+    config.summary_file = cli.options.summary_file.clone();
+    let json = config.message_format == MessageFormat::Json;
+
+    if let cli::Command::Config(cli::ConfigCommand::Show { origin }) = command {
+        // Show the values the build would use, including the profile's.
+        config.apply_profile(&mut origins)?;
+        show_config(&config, &origins, origin);
         return Ok(());
     }
 
     // Setup colored output
-    let use_colors = match config.color.as_str() {
+    // JSON output is for tools, so it leaves out everything else on stdout.
+    let use_colors = !json && match config.color.as_str() {
         "always" => true,
         "never" => false,
-        "auto" | _ => atty::is(atty::Stream::Stdout),
+        _ => atty::is(atty::Stream::Stdout),
     };
 
-    // Auto-detect configuration file if not specified
-    if config.config_file.is_none() {
-        if let Some(auto_config) = config::auto_detect_config() {
+    if cli.options.config_file.is_none() && !json {
+        if let Some(ref auto_config) = config.config_file {
             if use_colors {
                 println!("{} {}", "✓ Found config:".green(), auto_config.bright_blue());
             } else {
                 println!("✓ Found config: {}", auto_config);
             }
-            let file_config = config::load_config(&auto_config)?;
-            config.merge(file_config);
-            config.config_file = Some(auto_config);
         }
-    } else if let Some(ref conf_path) = config.config_file {
-        let file_config = config::load_config(conf_path)?;
-        config.merge(file_config);
     }
 
+    // A workspace root without sources of its own builds all of its members.
+    let has_sources = project.input_files.is_some() || project.bin.is_some() || project.staticlib.is_some() || project.sharedlib.is_some();
+    let virtual_workspace = project.workspace.is_some() && !has_sources;
+    if command.members().is_some() && (members.is_set() || virtual_workspace) {
+        let Some(ref workspace) = project.workspace else {
+            return Err(anyhow::anyhow!(
+                "--workspace and -p need a [workspace] section in the configuration file"
+            ));
+        };
+        let selected = workspace::select(workspace::members(Path::new("."), workspace)?, &members.packages)?;
+
+        let mut args = cli.options.to_args();
+        args.push(format!("--color={}", if use_colors { "always" } else { "never" }));
+        args.extend(command.member_args());
+        return workspace::run(&selected, &args, &config.reports, use_colors, json);
+    }
+
+    config.apply_profile(&mut origins)?;
+
     debug!("Effective configuration: {:?}", config);
 
-    // Validate configuration
-    config.validate()?;
+    // Validate configuration; checking doesn't link, so it needs no output file.
+    if let cli::Command::Check { .. } = command {
+        config.validate_sources()?;
+    } else {
+        config.validate()?;
+    }
 
-    // Watch mode
-    if config.watch {
-        return run_watch_mode(&config, use_colors);
+    if let cli::Command::Config(cli::ConfigCommand::Check) = command {
+        let checked: Vec<String> = layers
+            .iter()
+            .filter_map(|layer| match layer.source {
+                config::Source::File(ref path) => Some(path.clone()),
+                _ => None,
+            })
+            .collect();
+        if use_colors {
+            println!("{} Configuration is valid: {}", "✓".green().bold(), checked.join(", ").bright_blue());
+        } else {
+            println!("✓ Configuration is valid: {}", checked.join(", "));
+        }
+        return Ok(());
     }
 
     // Clean mode: remove generated object and binary files.
-    if config.clean {
+    if let cli::Command::Clean { .. } = command {
         clean_files(&config, use_colors)?;
         return Ok(());
     }
 
-    // Build
-    build_project(&config, use_colors)?;
+    if let Some(jobs) = config.jobs {
+        rayon::ThreadPoolBuilder::new().num_threads(jobs).build_global()?;
+    }
 
-    Ok(())
+    // Pick cross tools when the host's assembler and linker can't build the target
+    let tools = toolchain::resolve(&config)?;
+    config.assembler = tools.assembler;
+    config.linker = Some(tools.linker);
+    config.archiver = Some(tools.archiver);
+
+    match command {
+        cli::Command::Watch(_) => run_watch_mode(&config, use_colors),
+        cli::Command::Check { .. } => check_project(&config, use_colors),
+        cli::Command::Run(ref args) => {
+            build_project(&config, use_colors)?;
+            run_program(&config, args.bin.as_deref(), &args.args, &invocation_dir)
+        }
+        _ => build_project(&config, use_colors),
+    }
+}
+
+/// Print the effective configuration, optionally with the origin of each value
+fn show_config(config: &config::Config, origins: &config::Origins, with_origin: bool) {
+    let entries = config.entries();
+    let width = entries
+        .iter()
+        .map(|(key, value)| key.len() + value.len() + 3)
+        .max()
+        .unwrap_or(0);
+    for (key, value) in entries {
+        let line = format!("{} = {}", key, value);
+        match origins.get(key) {
+            Some(source) if with_origin => println!("{:<width$}  # {}", line, source, width = width),
+            // Values derived from other settings, such as the default build directory.
+            None if with_origin => println!("{:<width$}  # derived", line, width = width),
+            _ => println!("{}", line),
+        }
+    }
 }
 
 /// Clean generated files
-fn clean_files(config: &cli::Config, use_colors: bool) -> Result<()> {
-    for input in &config.input_files {
-        let obj_file = format!("{}.o", input);
+fn clean_files(config: &config::Config, use_colors: bool) -> Result<()> {
+    let artifacts = config.artifacts();
+    // Sources that no longer exist can't be expanded; fall back to the raw patterns.
+    let inputs: Vec<String> = artifacts
+        .iter()
+        .flat_map(|artifact| {
+            assembler::expand_globs(&artifact.input_files).unwrap_or_else(|_| artifact.input_files.clone())
+        })
+        .collect();
+    let mut removed = HashSet::new();
+    for input in &inputs {
+        let obj_file = assembler::object_path(input, config);
+        if !removed.insert(obj_file.clone()) {
+            continue;
+        }
         match fs::remove_file(&obj_file) {
             Ok(_) => {
                 if use_colors {
@@ -106,33 +278,91 @@ fn clean_files(config: &cli::Config, use_colors: bool) -> Result<()> {
             }
         }
     }
-    match fs::remove_file(&config.output_file) {
-        Ok(_) => {
-            if use_colors {
-                println!("{} {}", "✓ Removed:".green(), config.output_file.bright_black());
-            } else {
-                info!("Removed output binary: {}", config.output_file);
+    for output_file in artifacts.iter().map(|artifact| &artifact.output_file) {
+        match fs::remove_file(output_file) {
+            Ok(_) => {
+                if use_colors {
+                    println!("{} {}", "✓ Removed:".green(), output_file.bright_black());
+                } else {
+                    info!("Removed output binary: {}", output_file);
+                }
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                debug!("Output file not found (already cleaned): {}", output_file);
+            }
+            Err(e) => {
+                if use_colors {
+                    eprintln!("{} Failed to remove {}: {}", "⚠".yellow(), output_file, e);
+                } else {
+                    log::warn!("Failed to remove output file {}: {}", output_file, e);
+                }
             }
         }
-        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
-            debug!("Output file not found (already cleaned): {}", config.output_file);
-        }
-        Err(e) => {
-            if use_colors {
-                eprintln!("{} Failed to remove {}: {}", "⚠".yellow(), config.output_file, e);
-            } else {
-                log::warn!("Failed to remove output file {}: {}", config.output_file, e);
+    }
+    // Nothing else evicts cached objects, so clean empties the cache. The
+    // state directory stays, as it can hold the project's config.toml.
+    let state_dir = Path::new(cache::STATE_DIR);
+    for path in [state_dir.join("cache"), state_dir.join("build-state.json")] {
+        let removed = if path.is_dir() { fs::remove_dir_all(&path) } else { fs::remove_file(&path) };
+        match removed {
+            Ok(_) => {
+                if use_colors {
+                    println!("{} {}", "✓ Removed:".green(), path.display().bright_black());
+                } else {
+                    info!("Removed build cache: {}", path.display());
+                }
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                debug!("Build cache not found (already cleaned): {}", path.display());
+            }
+            Err(e) => {
+                if use_colors {
+                    eprintln!("{} Failed to remove {}: {}", "⚠".yellow(), path.display(), e);
+                } else {
+                    log::warn!("Failed to remove build cache {}: {}", path.display(), e);
+                }
             }
         }
     }
     Ok(())
 }
 
-/// Build the project
-fn build_project(config: &cli::Config, use_colors: bool) -> Result<()> {
-    // Expand glob patterns in input files
-    let expanded_inputs = assembler::expand_globs(&config.input_files)?;
-    
+/// Artifacts paired with their expanded sources
+type ArtifactSources = Vec<(config::Artifact, Vec<String>)>;
+
+/// Expand the sources of every artifact
+///
+/// # Returns
+/// Each artifact with its sources, and every source once, in order
+fn project_sources(config: &config::Config) -> Result<(ArtifactSources, Vec<String>)> {
+    let mut artifacts = Vec::new();
+    let mut sources = Vec::new();
+    let mut objects = HashSet::new();
+    for artifact in config.artifacts() {
+        let inputs = assembler::expand_globs(&artifact.input_files)?;
+        // Sources shared between artifacts are assembled once.
+        for input in &inputs {
+            if objects.insert(assembler::object_path(input, config)) {
+                sources.push(input.clone());
+            }
+        }
+        artifacts.push((artifact, inputs));
+    }
+    Ok((artifacts, sources))
+}
+
+/// Assemble every source, reusing up-to-date and cached objects
+///
+/// # Returns
+/// The object files, in source order, and the assembler's warnings
+fn assemble_project(
+    config: &config::Config,
+    expanded_inputs: &[String],
+    cache: &cache::BuildCache,
+    events: &Emitter,
+    use_colors: bool,
+) -> Result<(Vec<String>, Vec<Diagnostic>)> {
+
     if use_colors {
         println!(
             "{} {} file(s)",
@@ -157,68 +387,318 @@ fn build_project(config: &cli::Config, use_colors: bool) -> Result<()> {
         None
     };
 
-    // Assemble files in parallel
-    let object_files: Result<Vec<String>> = expanded_inputs
-        .par_iter()
-        .map(|input| {
-            let result = assembler::assemble(input, config);
-            if let Some(ref bar) = pb {
-                bar.inc(1);
-                if let Ok(ref obj) = result {
-                    bar.set_message(format!("✓ {}", obj));
-                }
+    // Assemble files in parallel, reusing up-to-date and cached objects.
+    // Without --keep-going, collecting into a Result stops at the first error.
+    let assemble = |input: &String| {
+        let started = Instant::now();
+        let result = assembler::assemble_cached(input, config, cache);
+        match result {
+            Ok(ref done) => events.emit(&Event::file_assembled(input, &done.object, done.status, started.elapsed())),
+            Err(_) => events.emit(&Event::file_failed(input, started.elapsed())),
+        }
+        if let Some(ref bar) = pb {
+            bar.inc(1);
+            if let Ok(ref done) = result {
+                let mark = match done.status {
+                    CacheStatus::UpToDate => "=",
+                    CacheStatus::Hit => "↺",
+                    CacheStatus::Miss => "✓",
+                };
+                bar.set_message(format!("{} {}", mark, done.object));
             }
-            result
-        })
-        .collect();
+        }
+        result
+    };
+    let results: Result<Vec<assembler::Assembled>> = if config.keep_going {
+        let mut assembled = Vec::new();
+        let mut errors = Vec::new();
+        for result in expanded_inputs.par_iter().map(assemble).collect::<Vec<_>>() {
+            match result {
+                Ok(done) => assembled.push(done),
+                Err(e) => errors.push(e),
+            }
+        }
+        if errors.is_empty() {
+            Ok(assembled)
+        } else {
+            // The files that did assemble may have warnings worth fixing too.
+            let warnings = assembled.into_iter().flat_map(|done| done.warnings).collect();
+            let _ = report_warnings(warnings, config, events, use_colors);
+            Err(diagnostics::Failures { errors, total: expanded_inputs.len() }.into())
+        }
+    } else {
+        expanded_inputs.par_iter().map(assemble).collect()
+    };
 
-    let object_files = object_files?;
+    // Keep whatever was recorded before a failure so those files aren't rebuilt.
+    let results = match results {
+        Ok(results) => results,
+        Err(e) => {
+            if !config.dry_run {
+                cache.save()?;
+            }
+            return Err(e);
+        }
+    };
+    let count = |status| results.iter().filter(|done| done.status == status).count();
+    let (assembled, restored) = (count(CacheStatus::Miss), count(CacheStatus::Hit));
+    let fresh = count(CacheStatus::UpToDate);
+    let mut object_files = Vec::new();
+    let mut warnings = Vec::new();
+    for done in results {
+        object_files.push(done.object);
+        warnings.extend(done.warnings);
+    }
 
     if let Some(ref bar) = pb {
         bar.finish_with_message("Assembly complete");
     }
 
-    // Link all object files into the final executable.
     if use_colors {
-        println!("{} {}", "→ Linking".bright_blue().bold(), config.output_file.bright_yellow());
+        println!(
+            "{} {} assembled, {} from cache, {} up to date",
+            "→".bright_blue().bold(),
+            assembled.to_string().bright_yellow(),
+            restored.to_string().bright_cyan(),
+            fresh.to_string().bright_black()
+        );
+    } else {
+        info!("{} assembled, {} from cache, {} up to date", assembled, restored, fresh);
     }
-    
-    linker::link(&object_files, config)?;
 
+    Ok((object_files, warnings))
+}
+
+/// Print the warnings of a build, each once, with their count
+///
+/// # Returns
+/// An error if there were warnings and `deny_warnings` is set
+fn report_warnings(mut warnings: Vec<Diagnostic>, config: &config::Config, events: &Emitter, use_colors: bool) -> Result<()> {
+    diagnostics::dedup(&mut warnings);
+    if warnings.is_empty() {
+        return Ok(());
+    }
+
+    for warning in &warnings {
+        events.emit(&Event::diagnostic(warning));
+    }
+    let count = format!("{} warning(s)", warnings.len());
+    // With JSON output, the events above already carry the warnings.
+    if !events.is_json() {
+        eprintln!("{}", diagnostics::render(&warnings));
+        if use_colors {
+            eprintln!("{} {}", "⚠".yellow().bold(), count.yellow().bold());
+        } else {
+            eprintln!("warning: {}", count);
+        }
+    }
+
+    if config.deny_warnings {
+        return Err(anyhow::anyhow!(
+            "Build failed: {} denied by `deny_warnings`\nFix them, or pass --no-deny-warnings to allow them",
+            count
+        ));
+    }
+    Ok(())
+}
+
+/// Assemble every input without linking, reporting the first error, or all
+/// of them with `--keep-going`
+fn check_project(config: &config::Config, use_colors: bool) -> Result<()> {
+    events::build(config, |events| check_sources(config, events, use_colors))
+}
+
+/// Assemble every input of `check_project`
+fn check_sources(config: &config::Config, events: &Emitter, use_colors: bool) -> Result<()> {
+    let cache = cache::BuildCache::open(cache::STATE_DIR);
+    let (_, sources) = project_sources(config)?;
+    let (object_files, warnings) = assemble_project(config, &sources, &cache, events, use_colors)?;
+    if !config.dry_run {
+        cache.save()?;
+    }
+    report_warnings(warnings, config, events, use_colors)?;
+
+    if use_colors {
+        println!(
+            "{} Check passed: {} file(s)",
+            "✓".green().bold(),
+            object_files.len().to_string().bright_green().bold()
+        );
+    } else {
+        info!("Check passed: {} file(s)", object_files.len());
+    }
+    Ok(())
+}
+
+/// Build the project
+fn build_project(config: &config::Config, use_colors: bool) -> Result<()> {
+    events::build(config, |events| build_artifacts(config, events, use_colors))
+}
+
+/// Assemble and link every artifact of `build_project`
+fn build_artifacts(config: &config::Config, events: &Emitter, use_colors: bool) -> Result<()> {
+    let cache = cache::BuildCache::open(cache::STATE_DIR);
+    let (artifacts, sources) = project_sources(config)?;
+    let (_, mut warnings) = assemble_project(config, &sources, &cache, events, use_colors)?;
+
+    let mut built = Vec::new();
+    for (artifact, inputs) in &artifacts {
+        let object_files: Vec<String> = inputs.iter().map(|input| assembler::object_path(input, config)).collect();
+
+        // Link only if the objects or link settings changed since the last link.
+        let link_key = linker::cache_key(artifact, &object_files, config, &cache);
+        let started = Instant::now();
+        if !config.force && cache.is_linked(&artifact.output_file, &link_key) {
+            warnings.extend(cache.warnings(&link_key));
+            let event = Event::link_finished(&artifact.name, artifact.kind, &artifact.output_file, true, started.elapsed());
+            events.emit(&event);
+            if use_colors {
+                println!(
+                    "{} Up to date: {}",
+                    "✓".green().bold(),
+                    artifact.output_file.bright_green().bold()
+                );
+            } else {
+                info!("Up to date: {}", artifact.output_file);
+            }
+            continue;
+        }
+
+        // Link the artifact's object files into its output.
+        if use_colors {
+            let action = match artifact.kind {
+                config::ArtifactKind::StaticLib => "→ Archiving",
+                _ => "→ Linking",
+            };
+            println!("{} {}", action.bright_blue().bold(), artifact.output_file.bright_yellow());
+        }
+
+        let link_warnings = match linker::link(artifact, &object_files, config) {
+            Ok(link_warnings) => link_warnings,
+            Err(e) => {
+                events.emit(&Event::link_failed(&artifact.name, artifact.kind, &artifact.output_file, started.elapsed()));
+                // Keep the artifacts linked so far.
+                if !config.dry_run {
+                    cache.save()?;
+                }
+                return Err(e);
+            }
+        };
+        if !config.dry_run {
+            cache.record_link(&artifact.output_file, &link_key);
+            cache.record_warnings(&link_key, &link_warnings);
+        }
+        let event = Event::link_finished(&artifact.name, artifact.kind, &artifact.output_file, false, started.elapsed());
+        events.emit(&event);
+        warnings.extend(link_warnings);
+        built.push(artifact.output_file.as_str());
+    }
+    if !config.dry_run {
+        cache.save()?;
+    }
+    report_warnings(warnings, config, events, use_colors)?;
+
+    if built.is_empty() {
+        return Ok(());
+    }
     if use_colors {
         println!(
             "{} Build complete: {}",
             "✓".green().bold(),
-            config.output_file.bright_green().bold()
+            built.join(", ").bright_green().bold()
         );
     } else {
-        info!("Build complete: {}", config.output_file);
+        info!("Build complete: {}", built.join(", "));
     }
 
     Ok(())
 }
 
+/// Run the built program with `args` in the directory RASM was started from,
+/// exiting with its exit code
+///
+/// # Arguments
+/// * `bin` - Name of the executable to run; may be omitted when there is only one
+fn run_program(config: &config::Config, bin: Option<&str>, args: &[String], invocation_dir: &Path) -> Result<()> {
+    let bins: Vec<config::Artifact> = config
+        .artifacts()
+        .into_iter()
+        .filter(|artifact| artifact.kind == config::ArtifactKind::Bin)
+        .collect();
+    let names = || bins.iter().map(|b| b.name.as_str()).collect::<Vec<_>>().join(", ");
+    let output_file = match (bin, bins.as_slice()) {
+        (None, [only]) => &only.output_file,
+        (None, []) => return Err(anyhow::anyhow!("The project has no executable to run")),
+        (None, _) => {
+            return Err(anyhow::anyhow!("Several executables could run: {}\nPick one with `--bin <NAME>`", names()));
+        }
+        (Some(name), _) => match bins.iter().find(|b| b.name == name) {
+            Some(found) => &found.output_file,
+            None => return Err(anyhow::anyhow!("No executable named '{}'\nExecutables: {}", name, names())),
+        },
+    };
+
+    let target = target::parse(&config.target)?;
+    if !target.is_host() {
+        return Err(anyhow::anyhow!(
+            "Cannot run {}: it was built for {}, not this host ({})",
+            output_file,
+            target.triple,
+            target::host().triple
+        ));
+    }
+
+    // Relative to the project root; a bare file name would otherwise be looked up on PATH.
+    let program = std::env::current_dir()?.join(output_file);
+
+    let mut cmd = std::process::Command::new(&program);
+    cmd.args(args).current_dir(invocation_dir);
+    if config.verbose || config.dry_run {
+        info!("Running {}", program.display());
+        debug!("Run command: {:?}", cmd);
+    }
+    if config.dry_run {
+        return Ok(());
+    }
+
+    let status = cmd
+        .status()
+        .map_err(|e| anyhow::anyhow!("Failed to run '{}': {}", program.display(), e))?;
+    if !status.success() {
+        std::process::exit(status.code().unwrap_or(1));
+    }
+    Ok(())
+}
+
 /// Run in watch mode
-fn run_watch_mode(config: &cli::Config, use_colors: bool) -> Result<()> {
+fn run_watch_mode(config: &config::Config, use_colors: bool) -> Result<()> {
     use notify_debouncer_full::DebounceEventResult;
     
     if use_colors {
         println!("{} {}", "👁".bright_yellow(), "Watching for changes...".bright_blue().bold());
-    } else {
+    } else if config.message_format == MessageFormat::Human {
         println!("Watching for changes...");
     }
 
     let (tx, rx) = mpsc::channel();
 
+    // Included files of any extension trigger a rebuild once the first build
+    // has recorded them as dependencies.
+    let dependencies: Arc<Mutex<HashSet<PathBuf>>> = Arc::new(Mutex::new(HashSet::new()));
+    let watched_deps = Arc::clone(&dependencies);
+
     let mut debouncer = new_debouncer(
         Duration::from_secs(1),
         None,
         move |result: DebounceEventResult| {
             if let Ok(events) = result {
+                let deps = watched_deps.lock().unwrap();
                 for event in events {
                     if event.paths.iter().any(|p| {
-                        p.extension().and_then(|s| s.to_str()) == Some("s") ||
-                        p.extension().and_then(|s| s.to_str()) == Some("asm")
+                        matches!(
+                            p.extension().and_then(|s| s.to_str()),
+                            Some("s" | "S" | "asm" | "inc" | "i" | "mac")
+                        ) || deps.contains(&p.canonicalize().unwrap_or_else(|_| p.clone()))
                     }) {
                         let _ = tx.send(());
                         break;
@@ -229,7 +709,27 @@ fn run_watch_mode(config: &cli::Config, use_colors: bool) -> Result<()> {
     )?;
 
     // Watch current directory
+    let cwd = std::env::current_dir()?.canonicalize()?;
     debouncer.watch(Path::new("."), RecursiveMode::Recursive)?;
+    let mut watched_dirs = HashSet::new();
+
+    // Refresh the dependency set after a build, watching any include
+    // directories outside the project.
+    let mut refresh_dependencies = |debouncer: &mut notify_debouncer_full::Debouncer<_, _>| {
+        let paths: HashSet<PathBuf> = cache::BuildCache::open(cache::STATE_DIR)
+            .dependency_paths()
+            .into_iter()
+            .filter_map(|p| p.canonicalize().ok())
+            .collect();
+        for dir in paths.iter().filter_map(|p| p.parent()) {
+            if !dir.starts_with(&cwd) && watched_dirs.insert(dir.to_path_buf()) {
+                if let Err(e) = debouncer.watch(dir, RecursiveMode::NonRecursive) {
+                    log::warn!("Failed to watch {}: {}", dir.display(), e);
+                }
+            }
+        }
+        *dependencies.lock().unwrap() = paths;
+    };
 
     // Initial build
     if let Err(e) = build_project(config, use_colors) {
@@ -239,13 +739,14 @@ fn run_watch_mode(config: &cli::Config, use_colors: bool) -> Result<()> {
             eprintln!("Build failed: {}", e);
         }
     }
+    refresh_dependencies(&mut debouncer);
 
     // Wait for changes
     loop {
         if rx.recv().is_ok() {
             if use_colors {
                 println!("\n{} Rebuilding...", "→".bright_blue().bold());
-            } else {
+            } else if config.message_format == MessageFormat::Human {
                 println!("\nRebuilding...");
             }
 
@@ -256,6 +757,7 @@ fn run_watch_mode(config: &cli::Config, use_colors: bool) -> Result<()> {
                     eprintln!("Build failed: {}", e);
                 }
             }
+            refresh_dependencies(&mut debouncer);
         }
     }
 }
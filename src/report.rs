@@ -0,0 +1,447 @@
+use crate::cache::KeyHasher;
+use crate::config::Config;
+use crate::diagnostics::{self, Diagnostic, Severity};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// A CI report format, chosen with `--report`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportFormat
+{
+    /// GitHub Actions workflow commands (`::error file=...::message`)
+    Github,
+    /// GitLab code quality JSON
+    Gitlab,
+    /// JUnit XML, with a testcase per assembled file and linked artifact
+    Junit,
+    /// SARIF 2.1.0 JSON
+    Sarif,
+}
+
+impl ReportFormat
+{
+    const ALL: [ReportFormat; 4] = [ReportFormat::Github, ReportFormat::Gitlab, ReportFormat::Junit, ReportFormat::Sarif];
+
+    pub fn name(self) -> &'static str
+    {
+        match self
+        {
+            ReportFormat::Github => "github",
+            ReportFormat::Gitlab => "gitlab",
+            ReportFormat::Junit => "junit",
+            ReportFormat::Sarif => "sarif",
+        }
+    }
+
+    /// File written when no path is given; None writes to stdout, where
+    /// GitHub Actions reads workflow commands.
+    fn default_path(self) -> Option<&'static str>
+    {
+        match self
+        {
+            ReportFormat::Github => None,
+            ReportFormat::Gitlab => Some("gl-code-quality-report.json"),
+            ReportFormat::Junit => Some("rasm-junit.xml"),
+            ReportFormat::Sarif => Some("rasm.sarif"),
+        }
+    }
+}
+
+/// A `--report FORMAT[=PATH]` request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReportRequest
+{
+    pub format: ReportFormat,
+    /// Where to write the report; None uses the format's default.
+    pub path: Option<PathBuf>,
+}
+
+impl FromStr for ReportRequest
+{
+    type Err = String;
+
+    fn from_str(value: &str) -> std::result::Result<Self, Self::Err>
+    {
+        let (name, path) = match value.split_once('=')
+        {
+            Some((name, path)) => (name, Some(PathBuf::from(path))),
+            None => (value, None),
+        };
+        let names: Vec<&str> = ReportFormat::ALL.iter().map(|format| format.name()).collect();
+        let format = ReportFormat::ALL
+            .into_iter()
+            .find(|format| format.name() == name)
+            .ok_or_else(|| format!("unknown report format '{}' (expected one of: {})", name, names.join(", ")))?;
+        Ok(ReportRequest { format, path })
+    }
+}
+
+impl fmt::Display for ReportRequest
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        match self.path
+        {
+            Some(ref path) => write!(f, "{}={}", self.format.name(), path.display()),
+            None => write!(f, "{}", self.format.name()),
+        }
+    }
+}
+
+impl ReportRequest
+{
+    /// Resolve a relative path against the directory rasm was started from,
+    /// since the build runs from the project root.
+    pub fn relative_to(&self, dir: &Path) -> ReportRequest
+    {
+        ReportRequest { format: self.format, path: self.path.as_ref().map(|path| dir.join(path)) }
+    }
+}
+
+/// One assembled file or linked artifact.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Step
+{
+    /// Source file or artifact output.
+    pub name: String,
+    pub duration_ms: f64,
+    pub failed: bool,
+}
+
+/// What a build did, collected from its events for the reports.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Summary
+{
+    pub files: Vec<Step>,
+    pub links: Vec<Step>,
+    pub diagnostics: Vec<Diagnostic>,
+    pub duration_ms: f64,
+}
+
+impl Summary
+{
+    /// Add the summary of a workspace member built in `dir`, making its
+    /// paths relative to the workspace root instead of the member.
+    pub fn merge(&mut self, member: Summary, dir: &Path)
+    {
+        let rebase = |path: &str| dir.join(path).to_string_lossy().into_owned();
+        let steps = |steps: Vec<Step>| steps.into_iter().map(|step| Step { name: rebase(&step.name), ..step });
+        self.files.extend(steps(member.files));
+        self.links.extend(steps(member.links));
+        self.diagnostics.extend(member.diagnostics.into_iter().map(|mut diagnostic| {
+            diagnostic.file = diagnostic.file.as_deref().map(rebase);
+            diagnostic
+        }));
+    }
+}
+
+/// Write the summary file and every report asked for in `config` from the
+/// summary of a build.
+pub fn write(config: &Config, summary: &Summary) -> Result<()>
+{
+    if let Some(ref path) = config.summary_file
+    {
+        std::fs::write(path, serde_json::to_string(summary)?)
+            .with_context(|| format!("Failed to write build summary {}", path.display()))?;
+    }
+    write_reports(&config.reports, summary)
+}
+
+/// Write each report in `requests` from `summary`.
+pub fn write_reports(requests: &[ReportRequest], summary: &Summary) -> Result<()>
+{
+    for request in requests
+    {
+        let text = match request.format
+        {
+            ReportFormat::Github => github(summary),
+            ReportFormat::Gitlab => serde_json::to_string_pretty(&gitlab(summary))?,
+            ReportFormat::Junit => junit(summary),
+            ReportFormat::Sarif => serde_json::to_string_pretty(&sarif(summary))?,
+        };
+        match request.path.as_deref().or(request.format.default_path().map(Path::new))
+        {
+            Some(path) => {
+                std::fs::write(path, text)
+                    .with_context(|| format!("Failed to write {} report {}", request.format.name(), path.display()))?;
+            }
+            None => print!("{}", text),
+        }
+    }
+    Ok(())
+}
+
+/// Path of `file` relative to the repository root named by `root_var`
+/// (such as GITHUB_WORKSPACE), so CI can place it; unchanged outside CI.
+fn repo_path(file: &str, root_var: &str) -> String
+{
+    let Some(root) = std::env::var_os(root_var) else
+    {
+        return file.to_string();
+    };
+    let absolute = std::env::current_dir().map(|dir| dir.join(file)).unwrap_or_else(|_| PathBuf::from(file));
+    match absolute.strip_prefix(&root)
+    {
+        Ok(relative) => relative.to_string_lossy().into_owned(),
+        Err(_) => file.to_string(),
+    }
+}
+
+/// Escape text for a workflow command; `property` also escapes the
+/// separators used between `key=value` properties.
+fn github_escape(text: &str, property: bool) -> String
+{
+    let mut escaped = text.replace('%', "%25").replace('\r', "%0D").replace('\n', "%0A");
+    if property
+    {
+        escaped = escaped.replace(':', "%3A").replace(',', "%2C");
+    }
+    escaped
+}
+
+/// GitHub Actions annotations, one workflow command per diagnostic.
+pub fn github(summary: &Summary) -> String
+{
+    let mut out = String::new();
+    for diagnostic in &summary.diagnostics
+    {
+        let command = match diagnostic.severity
+        {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+            Severity::Note => "notice",
+        };
+        let mut properties = Vec::new();
+        if let Some(ref file) = diagnostic.file
+        {
+            properties.push(format!("file={}", github_escape(&repo_path(file, "GITHUB_WORKSPACE"), true)));
+        }
+        if let Some(line) = diagnostic.line
+        {
+            properties.push(format!("line={}", line));
+        }
+        if let Some(column) = diagnostic.column
+        {
+            properties.push(format!("col={}", column));
+        }
+        properties.push("title=rasm".to_string());
+        out.push_str(&format!("::{} {}::{}\n", command, properties.join(","), github_escape(&diagnostic.message, false)));
+    }
+    out
+}
+
+/// GitLab code quality report: a JSON array of issues.
+pub fn gitlab(summary: &Summary) -> Value
+{
+    let issues: Vec<Value> = summary
+        .diagnostics
+        .iter()
+        .filter(|diagnostic| diagnostic.severity != Severity::Note)
+        .map(|diagnostic| {
+            let path = diagnostic.file.as_deref().map_or_else(String::new, |file| repo_path(file, "CI_PROJECT_DIR"));
+            // Same message on another line is another issue; 0 stands for
+            // an unknown line or column.
+            let mut fingerprint = KeyHasher::new();
+            fingerprint
+                .field(&path)
+                .field(diagnostic.line.unwrap_or(0).to_le_bytes())
+                .field(diagnostic.column.unwrap_or(0).to_le_bytes())
+                .field(diagnostic.message.as_bytes());
+            json!({
+                "description": diagnostic.message,
+                "check_name": format!("rasm-{}", diagnostic.severity.as_str()),
+                "fingerprint": fingerprint.finish(),
+                "severity": if diagnostic.severity == Severity::Error { "major" } else { "minor" },
+                "location": {
+                    "path": path,
+                    "lines": { "begin": diagnostic.line.unwrap_or(1) }
+                }
+            })
+        })
+        .collect();
+    Value::Array(issues)
+}
+
+fn xml_escape(text: &str) -> String
+{
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// JUnit XML with a testcase per assembled file and per linked artifact.
+/// A failed testcase carries the errors reported for that file.
+pub fn junit(summary: &Summary) -> String
+{
+    let steps = summary.files.iter().map(|step| ("assemble", step)).chain(summary.links.iter().map(|step| ("link", step)));
+    let failures = summary.files.iter().chain(&summary.links).filter(|step| step.failed).count();
+
+    let mut cases = String::new();
+    for (class, step) in steps
+    {
+        cases.push_str(&format!(
+            "    <testcase classname=\"{}\" name=\"{}\" time=\"{:.3}\"",
+            class,
+            xml_escape(&step.name),
+            step.duration_ms / 1000.0
+        ));
+        if !step.failed
+        {
+            cases.push_str("/>\n");
+            continue;
+        }
+        let errors: Vec<Diagnostic> = summary
+            .diagnostics
+            .iter()
+            .filter(|d| d.severity == Severity::Error && (class == "link" || d.file.as_deref() == Some(&step.name)))
+            .cloned()
+            .collect();
+        let message = match errors.first()
+        {
+            Some(first) => first.message.lines().next().unwrap_or_default().to_string(),
+            None => format!("Failed to {} {}", class, step.name),
+        };
+        cases.push_str(&format!(
+            ">\n      <failure message=\"{}\">{}</failure>\n    </testcase>\n",
+            xml_escape(&message),
+            xml_escape(&diagnostics::render(&errors))
+        ));
+    }
+
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <testsuites>\n  <testsuite name=\"rasm\" tests=\"{}\" failures=\"{}\" time=\"{:.3}\">\n{}  </testsuite>\n</testsuites>\n",
+        summary.files.len() + summary.links.len(),
+        failures,
+        summary.duration_ms / 1000.0,
+        cases
+    )
+}
+
+/// SARIF 2.1.0 log with one result per diagnostic.
+pub fn sarif(summary: &Summary) -> Value
+{
+    let results: Vec<Value> = summary
+        .diagnostics
+        .iter()
+        .map(|diagnostic| {
+            let mut result = json!({
+                "ruleId": format!("rasm/{}", diagnostic.severity.as_str()),
+                "level": diagnostic.severity.as_str(),
+                "message": { "text": diagnostic.message },
+            });
+            if let Some(ref file) = diagnostic.file
+            {
+                let mut region = json!({ "startLine": diagnostic.line.unwrap_or(1) });
+                if let Some(column) = diagnostic.column
+                {
+                    region["startColumn"] = json!(column);
+                }
+                result["locations"] = json!([{
+                    "physicalLocation": {
+                        "artifactLocation": { "uri": file, "uriBaseId": "SRCROOT" },
+                        "region": region
+                    }
+                }]);
+            }
+            result
+        })
+        .collect();
+
+    let root = std::env::current_dir().map(|dir| format!("file://{}/", dir.display())).unwrap_or_default();
+    json!({
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": "rasm",
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "rules": [
+                        { "id": "rasm/error", "shortDescription": { "text": "Assembler or linker error" } },
+                        { "id": "rasm/warning", "shortDescription": { "text": "Assembler or linker warning" } },
+                        { "id": "rasm/note", "shortDescription": { "text": "Assembler or linker note" } }
+                    ]
+                }
+            },
+            "originalUriBaseIds": { "SRCROOT": { "uri": root } },
+            "results": results
+        }]
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn summary() -> Summary {
+        let mut error = Diagnostic::new(Severity::Error, "no such instruction: `bad'");
+        error.file = Some("src/a.s".to_string());
+        error.line = Some(5);
+        let mut warning = Diagnostic::new(Severity::Warning, "100% sure, probably");
+        warning.file = Some("src/b.s".to_string());
+        warning.line = Some(2);
+        warning.column = Some(7);
+        Summary {
+            files: vec![
+                Step { name: "src/a.s".to_string(), duration_ms: 4.0, failed: true },
+                Step { name: "src/b.s".to_string(), duration_ms: 2.5, failed: false },
+            ],
+            links: Vec::new(),
+            diagnostics: vec![error, warning],
+            duration_ms: 10.0,
+        }
+    }
+
+    #[test]
+    fn test_parse_request() {
+        assert_eq!(
+            "junit=out/report.xml".parse::<ReportRequest>().unwrap(),
+            ReportRequest { format: ReportFormat::Junit, path: Some(PathBuf::from("out/report.xml")) }
+        );
+        let github: ReportRequest = "github".parse().unwrap();
+        assert_eq!(github.to_string(), "github");
+        assert!("xml".parse::<ReportRequest>().unwrap_err().contains("github, gitlab, junit, sarif"));
+    }
+
+    #[test]
+    fn test_report_formats() {
+        let summary = summary();
+
+        let annotations = github(&summary);
+        assert_eq!(
+            annotations,
+            "::error file=src/a.s,line=5,title=rasm::no such instruction: `bad'\n\
+             ::warning file=src/b.s,line=2,col=7,title=rasm::100%25 sure, probably\n"
+        );
+
+        let issues = gitlab(&summary);
+        assert_eq!(issues[0]["severity"], "major");
+        assert_eq!(issues[1]["location"]["lines"]["begin"], 2);
+        assert_ne!(issues[0]["fingerprint"], issues[1]["fingerprint"]);
+
+        let mut repeated = self::summary();
+        let mut again = repeated.diagnostics[0].clone();
+        again.line = Some(9);
+        repeated.diagnostics.push(again);
+        let issues = gitlab(&repeated);
+        assert_eq!(issues[0]["description"], issues[2]["description"]);
+        assert_ne!(issues[0]["fingerprint"], issues[2]["fingerprint"]);
+        assert_eq!(gitlab(&repeated)[2]["fingerprint"], issues[2]["fingerprint"]);
+
+        let xml = junit(&summary);
+        assert!(xml.contains("<testsuite name=\"rasm\" tests=\"2\" failures=\"1\" time=\"0.010\">"), "{}", xml);
+        assert!(xml.contains("<testcase classname=\"assemble\" name=\"src/b.s\" time=\"0.003\"/>"), "{}", xml);
+        assert!(xml.contains("<failure message=\"no such instruction: `bad'\">error: no such instruction"), "{}", xml);
+
+        let log = sarif(&summary);
+        let results = &log["runs"][0]["results"];
+        assert_eq!(results[0]["level"], "error");
+        assert_eq!(results[1]["locations"][0]["physicalLocation"]["region"]["startColumn"], 7);
+    }
+}
@@ -0,0 +1,252 @@
+use serde_json::{json, Value};
+
+/// Return the candidate closest to `name`, if any is a likely misspelling.
+pub fn suggest<'a>(name: &str, candidates: impl IntoIterator<Item = &'a str>) -> Option<&'a str>
+{
+    candidates
+        .into_iter()
+        .map(|candidate| (strsim::jaro_winkler(name, candidate), candidate))
+        .filter(|(score, _)| *score > 0.8)
+        .max_by(|a, b| a.0.total_cmp(&b.0))
+        .map(|(_, candidate)| candidate)
+}
+
+/// The backtick-quoted words in a serde error message, such as the unknown
+/// key and the expected keys of "unknown field `x`, expected `a` or `b`".
+fn quoted_words(message: &str) -> Vec<&str>
+{
+    message.split('`').skip(1).step_by(2).collect()
+}
+
+/// 1-based line and column of byte `offset` in `text`.
+fn line_col(text: &str, offset: usize) -> (usize, usize)
+{
+    let before = &text[..offset.min(text.len())];
+    let line = before.matches('\n').count() + 1;
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    (line, before[line_start..].chars().count() + 1)
+}
+
+/// Turn a TOML syntax or schema error into a `file:line:column` diagnostic
+/// that quotes the offending line, with a "did you mean" hint for
+/// misspelled keys.
+///
+/// # Arguments
+/// * `path` - Path of the configuration file, as shown to the user
+/// * `contents` - Text of the configuration file
+/// * `err` - Error returned by the TOML parser
+pub fn config_error(path: &str, contents: &str, err: &toml::de::Error) -> anyhow::Error
+{
+    let message = err.message().trim_end();
+    let mut help = None;
+
+    let summary = if message.starts_with("unknown field")
+    {
+        let words = quoted_words(message);
+        let key = words.first().copied().unwrap_or_default();
+        let expected = words.iter().skip(1).copied();
+        help = Some(match suggest(key, expected.clone())
+        {
+            Some(candidate) => format!("did you mean `{}`?", candidate),
+            None => format!(
+                "valid keys here are {}",
+                expected.map(|k| format!("`{}`", k)).collect::<Vec<_>>().join(", ")
+            ),
+        });
+        format!("unknown key `{}`", key)
+    } else {
+        message.replace('\n', ", ")
+    };
+
+    let mut text = match err.span()
+    {
+        Some(span) => {
+            let (line, col) = line_col(contents, span.start);
+            let source_line = contents.lines().nth(line - 1).unwrap_or_default();
+            let width = contents[span.clone()].lines().next().map_or(1, |s| s.chars().count().max(1));
+            let gutter = " ".repeat(line.to_string().len());
+            format!(
+                "{}:{}:{}: {}\n{} |\n{} | {}\n{} | {}{}",
+                path,
+                line,
+                col,
+                summary,
+                gutter,
+                line,
+                source_line,
+                gutter,
+                " ".repeat(col - 1),
+                "^".repeat(width)
+            )
+        }
+        None => format!("{}: {}", path, summary),
+    };
+    if let Some(help) = help
+    {
+        text.push_str(&format!("\nhelp: {}", help));
+    }
+    anyhow::anyhow!(text)
+}
+
+fn string_list(description: &str) -> Value
+{
+    json!({ "description": description, "type": "array", "items": { "type": "string" } })
+}
+
+fn string(description: &str) -> Value
+{
+    json!({ "description": description, "type": "string" })
+}
+
+fn boolean(description: &str) -> Value
+{
+    json!({ "description": description, "type": "boolean" })
+}
+
+fn artifacts(description: &str) -> Value
+{
+    json!({ "description": description, "type": "array", "items": { "$ref": "#/definitions/artifact" } })
+}
+
+/// JSON Schema for `.rasm.toml` and the user and system configuration files,
+/// for `rasm config schema`. Editors with TOML schema support use it to
+/// complete and check keys.
+pub fn json_schema() -> Value
+{
+    json!({
+        "$schema": "http://json-schema.org/draft-07/schema#",
+        "title": "RASM configuration",
+        "description": "Configuration file for the RASM assembler and linker (.rasm.toml)",
+        "type": "object",
+        "additionalProperties": false,
+        "properties": {
+            "extends": string("Base configuration file to build on, relative to this file"),
+            "include": string_list("Configuration files merged in order after `extends`, relative to this file"),
+            "input_files": string_list("Input assembly files (supports glob patterns)"),
+            "output_file": string("Output binary file"),
+            "extra_flags": string_list("Extra flags for the linker (passed as-is)"),
+            "assembler": string("Assembler to use: as, nasm, yasm, llvm-mc or fasm"),
+            "assembler_flags": string_list("Additional flags to pass to the assembler"),
+            "linker": string("Linker to use: ld, ld.lld, ld64.lld, mold, gold, or a cc/clang driver"),
+            "archiver": string("Archiver for static libraries: ar, llvm-ar, or a cross ar"),
+            "target": string("Target triple or architecture, e.g. \"aarch64-apple-darwin\" or \"x86_64\""),
+            "build_dir": string("Directory for object files, mirroring the source tree"),
+            "verbose": boolean("Enable verbose logging"),
+            "dry_run": boolean("Print commands without executing them"),
+            "deny_warnings": boolean("Fail the build when the assembler or linker prints warnings"),
+            "color": {
+                "description": "When to use colored output",
+                "enum": ["auto", "always", "never"]
+            },
+            "jobs": {
+                "description": "Number of files to assemble in parallel",
+                "type": "integer",
+                "minimum": 1
+            },
+            "profile": {
+                "description": "Build profiles, by name",
+                "type": "object",
+                "additionalProperties": { "$ref": "#/definitions/profile" }
+            },
+            "workspace": {
+                "description": "Projects built together by `rasm build --workspace`",
+                "type": "object",
+                "additionalProperties": false,
+                "required": ["members"],
+                "properties": {
+                    "members": string_list("Member project directories, relative to this file (supports glob patterns)"),
+                    "exclude": string_list("Directories matched by `members` that are not members")
+                }
+            },
+            "bin": artifacts("Executables, each built from its own sources"),
+            "staticlib": artifacts("Static libraries (lib<name>.a), each built from its own sources"),
+            "sharedlib": artifacts("Shared libraries (lib<name>.so or .dylib), each built from its own sources")
+        },
+        "definitions": {
+            "artifact": {
+                "type": "object",
+                "additionalProperties": false,
+                "required": ["name", "input_files"],
+                "properties": {
+                    "name": string("Name of the artifact, used for its default output file"),
+                    "input_files": string_list("Sources of this artifact (supports glob patterns)"),
+                    "output_file": string("Output file (default: derived from the name)"),
+                    "entry": string("Entry point symbol of an executable (default: the target's)"),
+                    "extra_flags": string_list("Linker flags appended to the top-level extra_flags"),
+                    "soname": string("Name a shared library is recorded under (default: its file name, \"@rpath/<file name>\" on macOS)"),
+                    "exported_symbols": string_list("Symbols a shared library exports; all others are hidden (default: every global symbol)")
+                }
+            },
+            "profile": {
+                "type": "object",
+                "additionalProperties": false,
+                "properties": {
+                    "inherits": string("Profile to inherit unset settings from (default: \"dev\")"),
+                    "assembler_flags": string_list("Assembler flags appended to the top-level assembler_flags"),
+                    "extra_flags": string_list("Linker flags appended to the top-level extra_flags"),
+                    "debug": boolean("Emit debug information when assembling"),
+                    "strip": boolean("Strip symbols from the linked output"),
+                    "build_dir": string("Directory for object files of this profile")
+                }
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{ArtifactTable, FileConfig, Profile};
+
+    /// Keys serde accepts for `T`, taken from its unknown-key error.
+    fn accepted_keys<T: serde::de::DeserializeOwned + std::fmt::Debug>() -> Vec<String> {
+        let err = toml::from_str::<T>("not_a_key = 1").unwrap_err();
+        quoted_words(err.message()).into_iter().skip(1).map(str::to_string).collect()
+    }
+
+    fn schema_keys(properties: &Value) -> Vec<String> {
+        properties.as_object().unwrap().keys().cloned().collect()
+    }
+
+    #[test]
+    fn test_schema_matches_config_keys() {
+        let schema = json_schema();
+        let mut keys = accepted_keys::<FileConfig>();
+        keys.sort();
+        assert_eq!(schema_keys(&schema["properties"]), keys);
+
+        let mut keys = accepted_keys::<Profile>();
+        keys.sort();
+        assert_eq!(schema_keys(&schema["definitions"]["profile"]["properties"]), keys);
+
+        let mut keys = accepted_keys::<ArtifactTable>();
+        keys.sort();
+        assert_eq!(schema_keys(&schema["definitions"]["artifact"]["properties"]), keys);
+    }
+
+    #[test]
+    fn test_unknown_key_diagnostic() {
+        let contents = "input_files = [\"a.s\"]\nouput_file = \"app\"\n";
+        let err = toml::from_str::<FileConfig>(contents).unwrap_err();
+        let text = config_error(".rasm.toml", contents, &err).to_string();
+        assert!(text.starts_with(".rasm.toml:2:1: unknown key `ouput_file`"), "{}", text);
+        assert!(text.contains("2 | ouput_file = \"app\""));
+        assert!(text.ends_with("help: did you mean `output_file`?"));
+    }
+
+    #[test]
+    fn test_wrong_type_diagnostic() {
+        let contents = "[profile.release]\nstrip = \"yes\"\n";
+        let err = toml::from_str::<FileConfig>(contents).unwrap_err();
+        let text = config_error("rasm.toml", contents, &err).to_string();
+        assert!(text.starts_with("rasm.toml:2:9: "), "{}", text);
+        assert!(text.contains("expected a boolean"));
+    }
+
+    #[test]
+    fn test_suggest() {
+        assert_eq!(suggest("assembler_flag", ["assembler", "assembler_flags"]), Some("assembler_flags"));
+        assert_eq!(suggest("relase", ["dev", "release"]), Some("release"));
+        assert_eq!(suggest("zzz", ["dev", "release"]), None);
+    }
+}
@@ -0,0 +1,360 @@
+use anyhow::Result;
+
+/// CPU architecture of a build target.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Arch
+{
+    X86_64,
+    X86,
+    Aarch64,
+    Riscv64,
+}
+
+/// Operating system of a build target.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Os
+{
+    Linux,
+    Macos,
+    Windows,
+    /// Bare metal, no operating system.
+    Bare,
+}
+
+/// Object file format produced for a target.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ObjectFormat
+{
+    Elf,
+    MachO,
+    Coff,
+}
+
+/// An entry in the built-in target database.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Target
+{
+    /// Canonical target triple.
+    pub triple: &'static str,
+    pub arch: Arch,
+    pub os: Os,
+    pub format: ObjectFormat,
+    pub pointer_width: u32,
+    /// Symbol the linker uses as the program entry point.
+    pub entry: &'static str,
+    /// Default assembler and linker names.
+    pub assembler: &'static str,
+    pub linker: &'static str,
+    /// Prefix of cross tools for this target, as in "aarch64-linux-gnu-as".
+    pub tool_prefix: &'static str,
+}
+
+/// Targets RASM knows how to build for.
+pub static TARGETS: &[Target] = &[
+    Target {
+        triple: "x86_64-unknown-linux-gnu",
+        arch: Arch::X86_64,
+        os: Os::Linux,
+        format: ObjectFormat::Elf,
+        pointer_width: 64,
+        entry: "_start",
+        assembler: "as",
+        linker: "ld",
+        tool_prefix: "x86_64-linux-gnu-",
+    },
+    Target {
+        triple: "i686-unknown-linux-gnu",
+        arch: Arch::X86,
+        os: Os::Linux,
+        format: ObjectFormat::Elf,
+        pointer_width: 32,
+        entry: "_start",
+        assembler: "as",
+        linker: "ld",
+        tool_prefix: "i686-linux-gnu-",
+    },
+    Target {
+        triple: "aarch64-unknown-linux-gnu",
+        arch: Arch::Aarch64,
+        os: Os::Linux,
+        format: ObjectFormat::Elf,
+        pointer_width: 64,
+        entry: "_start",
+        assembler: "as",
+        linker: "ld",
+        tool_prefix: "aarch64-linux-gnu-",
+    },
+    Target {
+        triple: "riscv64-unknown-linux-gnu",
+        arch: Arch::Riscv64,
+        os: Os::Linux,
+        format: ObjectFormat::Elf,
+        pointer_width: 64,
+        entry: "_start",
+        assembler: "as",
+        linker: "ld",
+        tool_prefix: "riscv64-linux-gnu-",
+    },
+    Target {
+        triple: "x86_64-apple-darwin",
+        arch: Arch::X86_64,
+        os: Os::Macos,
+        format: ObjectFormat::MachO,
+        pointer_width: 64,
+        entry: "_start",
+        assembler: "as",
+        linker: "clang",
+        tool_prefix: "",
+    },
+    Target {
+        triple: "aarch64-apple-darwin",
+        arch: Arch::Aarch64,
+        os: Os::Macos,
+        format: ObjectFormat::MachO,
+        pointer_width: 64,
+        entry: "_start",
+        assembler: "as",
+        linker: "clang",
+        tool_prefix: "",
+    },
+    Target {
+        triple: "x86_64-pc-windows-gnu",
+        arch: Arch::X86_64,
+        os: Os::Windows,
+        format: ObjectFormat::Coff,
+        pointer_width: 64,
+        entry: "mainCRTStartup",
+        assembler: "as",
+        linker: "ld",
+        tool_prefix: "x86_64-w64-mingw32-",
+    },
+    Target {
+        triple: "i686-pc-windows-gnu",
+        arch: Arch::X86,
+        os: Os::Windows,
+        format: ObjectFormat::Coff,
+        pointer_width: 32,
+        entry: "_mainCRTStartup",
+        assembler: "as",
+        linker: "ld",
+        tool_prefix: "i686-w64-mingw32-",
+    },
+    Target {
+        triple: "x86_64-unknown-none-elf",
+        arch: Arch::X86_64,
+        os: Os::Bare,
+        format: ObjectFormat::Elf,
+        pointer_width: 64,
+        entry: "_start",
+        assembler: "as",
+        linker: "ld",
+        tool_prefix: "x86_64-elf-",
+    },
+    Target {
+        triple: "aarch64-unknown-none-elf",
+        arch: Arch::Aarch64,
+        os: Os::Bare,
+        format: ObjectFormat::Elf,
+        pointer_width: 64,
+        entry: "_start",
+        assembler: "as",
+        linker: "ld",
+        tool_prefix: "aarch64-none-elf-",
+    },
+    Target {
+        triple: "riscv64-unknown-elf",
+        arch: Arch::Riscv64,
+        os: Os::Bare,
+        format: ObjectFormat::Elf,
+        pointer_width: 64,
+        entry: "_start",
+        assembler: "as",
+        linker: "ld",
+        tool_prefix: "riscv64-unknown-elf-",
+    },
+];
+
+impl Arch
+{
+    /// Architecture name as used in LLVM triples.
+    pub fn triple_name(self) -> &'static str
+    {
+        match self
+        {
+            Arch::X86_64 => "x86_64",
+            Arch::X86 => "i686",
+            Arch::Aarch64 => "aarch64",
+            Arch::Riscv64 => "riscv64",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Arch>
+    {
+        match name
+        {
+            "x86_64" | "amd64" | "x64" => Some(Arch::X86_64),
+            "i386" | "i486" | "i586" | "i686" | "x86" => Some(Arch::X86),
+            "aarch64" | "arm64" => Some(Arch::Aarch64),
+            "riscv64" | "riscv64gc" | "riscv64imac" => Some(Arch::Riscv64),
+            _ => None,
+        }
+    }
+}
+
+impl ObjectFormat
+{
+    pub fn name(self) -> &'static str
+    {
+        match self
+        {
+            ObjectFormat::Elf => "elf",
+            ObjectFormat::MachO => "mach-o",
+            ObjectFormat::Coff => "coff",
+        }
+    }
+}
+
+impl Target
+{
+    /// Whether this target is the machine RASM is running on.
+    pub fn is_host(&self) -> bool
+    {
+        std::ptr::eq(self, host())
+    }
+}
+
+fn host_os() -> Os
+{
+    if cfg!(target_os = "macos")
+    {
+        Os::Macos
+    } else if cfg!(target_os = "windows") {
+        Os::Windows
+    } else {
+        Os::Linux
+    }
+}
+
+/// The target describing the machine RASM is running on.
+/// Falls back to x86_64 Linux on hosts missing from the table.
+pub fn host() -> &'static Target
+{
+    let arch = Arch::from_name(std::env::consts::ARCH).unwrap_or(Arch::X86_64);
+    lookup(arch, host_os())
+        .or_else(|| lookup(Arch::X86_64, Os::Linux))
+        .expect("target table contains x86_64 Linux")
+}
+
+fn lookup(arch: Arch, os: Os) -> Option<&'static Target>
+{
+    TARGETS.iter().find(|t| t.arch == arch && t.os == os)
+}
+
+/// Parse a target triple or shorthand into an entry of the target database.
+///
+/// Accepts canonical triples ("aarch64-apple-darwin"), partial triples
+/// ("i686-linux", "riscv64-unknown-elf") and bare architectures ("arm64"),
+/// which use the host operating system.
+pub fn parse(name: &str) -> Result<&'static Target>
+{
+    let lower = name.to_ascii_lowercase();
+    if let Some(target) = TARGETS.iter().find(|t| t.triple == lower)
+    {
+        return Ok(target);
+    }
+
+    let mut parts = lower.split('-');
+    let arch_name = parts.next().unwrap_or_default();
+    let Some(arch) = Arch::from_name(arch_name) else {
+        return Err(anyhow::anyhow!(
+            "Unknown target architecture '{}' in target '{}'\nRun `rasm --print target-list` to see supported targets",
+            arch_name,
+            name
+        ));
+    };
+
+    let mut os = None;
+    for part in parts
+    {
+        os = match part
+        {
+            "linux" => Some(Os::Linux),
+            "darwin" | "macos" | "apple" => Some(Os::Macos),
+            "windows" | "mingw32" | "w64" | "win32" => Some(Os::Windows),
+            "none" | "elf" => os.or(Some(Os::Bare)),
+            _ => continue,
+        };
+    }
+    let os = os.unwrap_or_else(host_os);
+
+    lookup(arch, os).ok_or_else(|| {
+        anyhow::anyhow!(
+            "Unsupported target '{}'\nRun `rasm --print target-list` to see supported targets",
+            name
+        )
+    })
+}
+
+/// Render the target database as a table for `rasm --print target-list`.
+pub fn target_list() -> String
+{
+    let mut out = format!(
+        "{:<28} {:<7} {:>4}  {:<16} {:<6} {:<6} {}\n",
+        "TRIPLE", "FORMAT", "BITS", "ENTRY", "AS", "LD", "CROSS PREFIX"
+    );
+    for t in TARGETS
+    {
+        let marker = if t.is_host() { " (host)" } else { "" };
+        out.push_str(&format!(
+            "{:<28} {:<7} {:>4}  {:<16} {:<6} {:<6} {}{}\n",
+            t.triple,
+            t.format.name(),
+            t.pointer_width,
+            t.entry,
+            t.assembler,
+            t.linker,
+            if t.tool_prefix.is_empty() { "-" } else { t.tool_prefix },
+            marker
+        ));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_full_triples() {
+        for t in TARGETS {
+            assert_eq!(parse(t.triple).unwrap().triple, t.triple);
+        }
+    }
+
+    #[test]
+    fn test_parse_partial_triples() {
+        assert_eq!(parse("i686-linux").unwrap().triple, "i686-unknown-linux-gnu");
+        assert_eq!(parse("arm64-apple-macos").unwrap().triple, "aarch64-apple-darwin");
+        assert_eq!(parse("x86_64-w64-mingw32").unwrap().triple, "x86_64-pc-windows-gnu");
+        assert_eq!(parse("riscv64gc-unknown-linux-gnu").unwrap().triple, "riscv64-unknown-linux-gnu");
+        assert_eq!(parse("aarch64-none-elf").unwrap().os, Os::Bare);
+    }
+
+    #[test]
+    fn test_parse_bare_arch_uses_host_os() {
+        assert_eq!(parse("arm64").unwrap().arch, Arch::Aarch64);
+        assert_eq!(parse("amd64").unwrap().os, host_os());
+    }
+
+    #[test]
+    fn test_parse_unknown() {
+        assert!(parse("sparc-unknown-linux-gnu").is_err());
+        assert!(parse("riscv64-apple-darwin").is_err());
+    }
+
+    #[test]
+    fn test_host_is_in_table() {
+        assert!(host().is_host());
+        assert!(target_list().contains("(host)"));
+    }
+}
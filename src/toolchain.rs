@@ -0,0 +1,231 @@
+use crate::config::{ArtifactKind, Config};
+use crate::linker;
+use crate::target::{self, Arch, ObjectFormat, Target};
+use anyhow::Result;
+use log::debug;
+use std::path::{Path, PathBuf};
+
+/// The assembler, linker and archiver programs chosen for a build.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Toolchain
+{
+    pub assembler: String,
+    pub linker: String,
+    pub archiver: String,
+}
+
+/// Find an executable on PATH, or check that an explicit path exists.
+pub fn find_in_path(tool: &str) -> Option<PathBuf>
+{
+    let path = Path::new(tool);
+    if path.components().count() > 1
+    {
+        return path.is_file().then(|| path.to_path_buf());
+    }
+    let exe_suffix = std::env::consts::EXE_SUFFIX;
+    std::env::var_os("PATH").and_then(|paths| {
+        std::env::split_paths(&paths)
+            .flat_map(|dir| [dir.join(tool), dir.join(format!("{}{}", tool, exe_suffix))])
+            .find(|candidate| candidate.is_file())
+    })
+}
+
+/// Whether the host's own tools can produce objects for `target`.
+/// Apple's tools cover every Mach-O architecture through `-arch`.
+fn host_tools_support(target: &Target) -> bool
+{
+    target.is_host()
+        || (target.format == ObjectFormat::MachO && target::host().format == ObjectFormat::MachO)
+}
+
+/// Whether `target` is `host`'s system with the other width of its
+/// architecture, like i686 Linux on x86-64 Linux. The host binutils handle
+/// these once given `--32` and `-m elf_i386`.
+fn other_width_of(target: &Target, host: &Target) -> bool
+{
+    let x86 = |arch: Arch| matches!(arch, Arch::X86 | Arch::X86_64);
+    target.arch != host.arch && target.os == host.os && target.format == host.format && x86(target.arch) && x86(host.arch)
+}
+
+/// Append the host's own `tool` to `names` when it can build for `target`,
+/// as the last resort before giving up.
+fn push_host_tool(names: &mut Vec<String>, target: &Target, tool: &str)
+{
+    if other_width_of(target, target::host())
+    {
+        names.push(tool.to_string());
+    }
+}
+
+/// Cross tool names to try for `tool` ("as", "ld" or "ar"), most specific first:
+/// the target's usual prefix, then the full triple as a prefix.
+fn prefixed_candidates(target: &Target, tool: &str) -> Vec<String>
+{
+    let mut names = Vec::new();
+    if !target.tool_prefix.is_empty()
+    {
+        names.push(format!("{}{}", target.tool_prefix, tool));
+    }
+    let triple_name = format!("{}-{}", target.triple, tool);
+    if !names.contains(&triple_name)
+    {
+        names.push(triple_name);
+    }
+    names
+}
+
+/// Candidate assemblers for a non-host target, with the LLVM fallback
+/// and then the host's `as`, when it can assemble for the target, last.
+pub fn assembler_candidates(target: &Target) -> Vec<String>
+{
+    let mut names = prefixed_candidates(target, "as");
+    names.push("llvm-mc".to_string());
+    push_host_tool(&mut names, target, target.assembler);
+    names
+}
+
+/// Candidate linkers for a non-host target, with the LLVM fallback where
+/// one accepts the same arguments and then the host's linker, when it can
+/// link for the target, last.
+pub fn linker_candidates(target: &Target) -> Vec<String>
+{
+    let mut names = prefixed_candidates(target, "ld");
+    match target.format
+    {
+        ObjectFormat::MachO => names.push("ld64.lld".to_string()),
+        ObjectFormat::Elf => names.push("ld.lld".to_string()),
+        // lld-link takes MSVC-style arguments, so there is no drop-in fallback.
+        ObjectFormat::Coff => {}
+    }
+    push_host_tool(&mut names, target, target.linker);
+    names
+}
+
+/// Candidate archivers for a non-host target, with `llvm-ar`, which
+/// handles every object format, and then the host's `ar` last.
+pub fn archiver_candidates(target: &Target) -> Vec<String>
+{
+    let mut names = prefixed_candidates(target, "ar");
+    names.push("llvm-ar".to_string());
+    push_host_tool(&mut names, target, "ar");
+    names
+}
+
+/// Return the first candidate found on PATH, or an error naming every
+/// program that was searched for.
+fn search(kind: &str, target: &Target, candidates: &[String]) -> Result<String>
+{
+    for candidate in candidates
+    {
+        if find_in_path(candidate).is_some()
+        {
+            debug!("Using {} {} for target {}", kind, candidate, target.triple);
+            return Ok(candidate.clone());
+        }
+    }
+    Err(anyhow::anyhow!(
+        "No {} found for target {}\nSearched PATH for: {}\nInstall a cross toolchain or set `{}` explicitly",
+        kind,
+        target.triple,
+        candidates.join(", "),
+        kind
+    ))
+}
+
+/// Choose the assembler and linker for the configured target.
+///
+/// Explicit settings are used as given. For targets the host tools can't
+/// handle, the default `as`, linker and `ar` are replaced by prefixed cross
+/// tools (`aarch64-linux-gnu-as`) or by `llvm-mc`/`ld.lld`/`llvm-ar`, so a
+/// missing cross toolchain fails up front instead of producing
+/// wrong-architecture errors. An archiver is only looked for when the
+/// project builds a static library.
+pub fn resolve(config: &Config) -> Result<Toolchain>
+{
+    let target = target::parse(&config.target)?;
+    let default_linker = config.linker.is_none();
+    let mut toolchain = Toolchain {
+        assembler: config.assembler.clone(),
+        linker: linker::linker_program(config, target),
+        archiver: linker::archiver_program(config),
+    };
+
+    if host_tools_support(target)
+    {
+        return Ok(toolchain);
+    }
+
+    if config.assembler == target.assembler
+    {
+        toolchain.assembler = search("assembler", target, &assembler_candidates(target))?;
+    }
+    if default_linker
+    {
+        toolchain.linker = search("linker", target, &linker_candidates(target))?;
+    }
+    let archives = config.artifacts().iter().any(|artifact| artifact.kind == ArtifactKind::StaticLib);
+    if config.archiver.is_none() && archives
+    {
+        toolchain.archiver = search("archiver", target, &archiver_candidates(target))?;
+    }
+
+    Ok(toolchain)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cross_candidates() {
+        let aarch64 = target::parse("aarch64-unknown-linux-gnu").unwrap();
+        assert_eq!(
+            assembler_candidates(aarch64),
+            vec!["aarch64-linux-gnu-as", "aarch64-unknown-linux-gnu-as", "llvm-mc"]
+        );
+        assert_eq!(
+            linker_candidates(aarch64),
+            vec!["aarch64-linux-gnu-ld", "aarch64-unknown-linux-gnu-ld", "ld.lld"]
+        );
+        assert_eq!(
+            archiver_candidates(aarch64),
+            vec!["aarch64-linux-gnu-ar", "aarch64-unknown-linux-gnu-ar", "llvm-ar"]
+        );
+
+        let riscv = target::parse("riscv64-unknown-elf").unwrap();
+        assert_eq!(assembler_candidates(riscv), vec!["riscv64-unknown-elf-as", "llvm-mc"]);
+    }
+
+    #[test]
+    fn test_other_width_uses_host_tools() {
+        let parse = |triple: &str| target::parse(triple).unwrap();
+        let x86_64_linux = parse("x86_64-unknown-linux-gnu");
+        assert!(other_width_of(parse("i686-unknown-linux-gnu"), x86_64_linux));
+        assert!(other_width_of(x86_64_linux, parse("i686-linux")));
+        assert!(!other_width_of(x86_64_linux, x86_64_linux));
+        assert!(!other_width_of(parse("i686-pc-windows-gnu"), x86_64_linux));
+        assert!(!other_width_of(parse("aarch64-linux"), x86_64_linux));
+
+        let i686 = parse("i686-unknown-linux-gnu");
+        let linkers = linker_candidates(i686);
+        assert_eq!(&linkers[..3], ["i686-linux-gnu-ld", "i686-unknown-linux-gnu-ld", "ld.lld"]);
+        if target::host() == x86_64_linux {
+            assert_eq!(linkers[3..], ["ld"]);
+            assert_eq!(assembler_candidates(i686).last().map(String::as_str), Some("as"));
+        }
+    }
+
+    #[test]
+    fn test_search_error_lists_candidates() {
+        let target = target::parse("riscv64-unknown-elf").unwrap();
+        let candidates = vec!["rasm-test-missing-as".to_string(), "rasm-test-missing-mc".to_string()];
+        let err = search("assembler", target, &candidates).unwrap_err().to_string();
+        assert!(err.contains("riscv64-unknown-elf"));
+        assert!(err.contains("rasm-test-missing-as, rasm-test-missing-mc"));
+    }
+
+    #[test]
+    fn test_host_target_keeps_configured_tools() {
+        assert!(host_tools_support(target::host()));
+    }
+}
@@ -0,0 +1,383 @@
+use crate::config::{self, Workspace};
+use crate::diagnostics::{Diagnostic, Severity};
+use crate::report::{self, ReportRequest, Summary};
+use anyhow::Result;
+use owo_colors::OwoColorize;
+use rayon::prelude::*;
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Instant;
+
+/// A project in a workspace, named after its directory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Member
+{
+    pub name: String,
+    pub dir: PathBuf,
+}
+
+/// Whether `dir` has a configuration file of its own.
+fn has_config(dir: &Path) -> bool
+{
+    config::CONFIG_FILE_NAMES.iter().any(|name| dir.join(name).is_file())
+}
+
+/// Expand a workspace pattern relative to `root` into the directories it names.
+fn expand(root: &Path, pattern: &str) -> Result<Vec<PathBuf>>
+{
+    let full = root.join(pattern);
+    let paths = glob::glob(&full.to_string_lossy())
+        .map_err(|e| anyhow::anyhow!("Invalid workspace pattern '{}': {}", pattern, e))?;
+    Ok(paths.filter_map(Result::ok).filter(|path| path.is_dir()).collect())
+}
+
+/// Find the members of `workspace`, whose configuration file is in `root`.
+///
+/// Directories matched by a glob pattern are skipped when they have no
+/// configuration file, so `members = ["*"]` can sit next to shared
+/// directories. A directory named without wildcards must be a project.
+///
+/// # Returns
+/// The members, sorted by name
+pub fn members(root: &Path, workspace: &Workspace) -> Result<Vec<Member>>
+{
+    let mut excluded = Vec::new();
+    for pattern in &workspace.exclude
+    {
+        excluded.extend(expand(root, pattern)?);
+    }
+
+    let mut found: BTreeMap<String, PathBuf> = BTreeMap::new();
+    for pattern in &workspace.members
+    {
+        let is_glob = pattern.contains(['*', '?', '[']);
+        let dirs = expand(root, pattern)?;
+        if dirs.is_empty() && !is_glob
+        {
+            return Err(anyhow::anyhow!("Workspace member '{}' does not exist", pattern));
+        }
+
+        for dir in dirs.into_iter().filter(|dir| !excluded.contains(dir))
+        {
+            if !has_config(&dir)
+            {
+                if is_glob
+                {
+                    continue;
+                }
+                return Err(anyhow::anyhow!("Workspace member '{}' has no .rasm.toml", pattern));
+            }
+
+            let name = dir
+                .canonicalize()?
+                .file_name()
+                .map(|n| n.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            match found.get(&name)
+            {
+                Some(existing) if *existing != dir => {
+                    return Err(anyhow::anyhow!(
+                        "Two workspace members are named '{}': {} and {}",
+                        name,
+                        existing.display(),
+                        dir.display()
+                    ));
+                }
+                _ => {
+                    found.insert(name, dir);
+                }
+            }
+        }
+    }
+
+    if found.is_empty()
+    {
+        return Err(anyhow::anyhow!("The workspace has no members"));
+    }
+    Ok(found.into_iter().map(|(name, dir)| Member { name, dir }).collect())
+}
+
+/// Pick the members named by `-p`, or all of them when none are named.
+pub fn select(members: Vec<Member>, packages: &[String]) -> Result<Vec<Member>>
+{
+    if packages.is_empty()
+    {
+        return Ok(members);
+    }
+
+    let mut selected = Vec::new();
+    for package in packages
+    {
+        match members.iter().find(|member| member.name == *package)
+        {
+            Some(member) => {
+                if !selected.contains(member)
+                {
+                    selected.push(member.clone());
+                }
+            }
+            None => {
+                let names: Vec<&str> = members.iter().map(|m| m.name.as_str()).collect();
+                let mut message = format!("No workspace member named '{}'", package);
+                if let Some(candidate) = crate::schema::suggest(package, names.iter().copied())
+                {
+                    message.push_str(&format!("\nhelp: did you mean `{}`?", candidate));
+                }
+                message.push_str(&format!("\nMembers: {}", names.join(", ")));
+                return Err(anyhow::anyhow!(message));
+            }
+        }
+    }
+    Ok(selected)
+}
+
+/// Find the workspace enclosing the project configured by `config_file`:
+/// the file itself if it has a `[workspace]` table, otherwise the nearest
+/// configuration file above it that does.
+///
+/// # Returns
+/// Path to the workspace's configuration file, or None outside a workspace
+pub fn find_root(config_file: &str) -> Result<Option<String>>
+{
+    let mut path = config_file.to_string();
+    loop
+    {
+        if config::load_config(&path)?.workspace.is_some()
+        {
+            return Ok(Some(path));
+        }
+
+        let root = config::project_root(&path);
+        let parent = if root == Path::new(".") { PathBuf::from("..") } else { root.join("..") };
+        match config::find_config(&parent)
+        {
+            Some(found) => path = parent.join(found).to_string_lossy().into_owned(),
+            None => return Ok(None),
+        }
+    }
+}
+
+/// Print the line that starts a member's block of output.
+fn print_header(name: &str, use_colors: bool)
+{
+    if use_colors
+    {
+        println!("{} {}", "──".bright_black(), name.bright_blue().bold());
+    } else {
+        println!("── {}", name);
+    }
+}
+
+/// File a member writes its build summary to, for the combined reports.
+fn summary_file(member: &Member) -> PathBuf
+{
+    std::env::temp_dir().join(format!("rasm-summary-{}-{}.json", std::process::id(), member.name))
+}
+
+/// Combine the summaries the members wrote into one for the workspace.
+/// A member that failed without writing one, such as on a configuration
+/// error, is reported as an error of its own.
+fn combine_summaries(results: &[(&Member, bool)]) -> Summary
+{
+    let mut combined = Summary::default();
+    for (member, ok) in results
+    {
+        let path = summary_file(member);
+        let summary = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|text| serde_json::from_str::<Summary>(&text).ok());
+        let _ = std::fs::remove_file(&path);
+        match summary
+        {
+            Some(summary) => combined.merge(summary, member.dir.strip_prefix(".").unwrap_or(&member.dir)),
+            None if !ok => combined.diagnostics.push(Diagnostic::new(
+                Severity::Error,
+                format!("Workspace member '{}' failed before building", member.name),
+            )),
+            None => {}
+        }
+    }
+    combined
+}
+
+/// Run rasm with `args` in every member, in parallel.
+///
+/// Each member's output is captured and printed as one block when it
+/// finishes, followed by a combined summary. With `json`, the members'
+/// event streams are passed through without headers or summary. The
+/// `reports` cover all members, with paths relative to the workspace root.
+///
+/// # Returns
+/// An error naming the members that failed, if any
+pub fn run(members: &[Member], args: &[String], reports: &[ReportRequest], use_colors: bool, json: bool) -> Result<()>
+{
+    let exe = std::env::current_exe()?;
+    let print_lock = Mutex::new(());
+    let started = Instant::now();
+
+    let results: Vec<(&Member, bool)> = members
+        .par_iter()
+        .map(|member| {
+            let mut command = std::process::Command::new(&exe);
+            if !reports.is_empty()
+            {
+                command.arg(format!("--summary-file={}", summary_file(member).display()));
+            }
+            let output = command.args(args).current_dir(&member.dir).output();
+
+            let _guard = print_lock.lock().unwrap();
+            if !json
+            {
+                print_header(&member.name, use_colors);
+            }
+            let success = match output
+            {
+                Ok(output) => {
+                    let _ = std::io::stdout().write_all(&output.stdout);
+                    let _ = std::io::stderr().write_all(&output.stderr);
+                    output.status.success()
+                }
+                Err(e) => {
+                    eprintln!("Failed to run rasm in {}: {}", member.dir.display(), e);
+                    false
+                }
+            };
+            if !json
+            {
+                println!();
+            }
+            (member, success)
+        })
+        .collect();
+
+    let written = if reports.is_empty()
+    {
+        Ok(())
+    } else {
+        let mut summary = combine_summaries(&results);
+        summary.duration_ms = started.elapsed().as_secs_f64() * 1000.0;
+        report::write_reports(reports, &summary)
+    };
+
+    let failed: Vec<&str> = results.iter().filter(|(_, ok)| !ok).map(|(member, _)| member.name.as_str()).collect();
+    let passed = results.len() - failed.len();
+    if json
+    {
+        // Each member's build-finished event already tells how it went.
+    } else if use_colors
+    {
+        println!(
+            "{} Workspace: {} succeeded, {} failed",
+            "→".bright_blue().bold(),
+            passed.to_string().bright_green(),
+            failed.len().to_string().bright_red()
+        );
+        for (member, ok) in &results
+        {
+            if *ok
+            {
+                println!("  {} {}", "✓".green(), member.name);
+            } else {
+                println!("  {} {}", "✗".red(), member.name.red());
+            }
+        }
+    } else {
+        println!("Workspace: {} succeeded, {} failed", passed, failed.len());
+        for (member, ok) in &results
+        {
+            println!("  {} {}", if *ok { "✓" } else { "✗" }, member.name);
+        }
+    }
+
+    if failed.is_empty()
+    {
+        written
+    } else {
+        if let Err(e) = written
+        {
+            log::warn!("{:#}", e);
+        }
+        Err(anyhow::anyhow!(
+            "{} of {} workspace members failed: {}",
+            failed.len(),
+            results.len(),
+            failed.join(", ")
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn workspace(members: &[&str], exclude: &[&str]) -> Workspace {
+        Workspace {
+            members: members.iter().map(|s| s.to_string()).collect(),
+            exclude: exclude.iter().map(|s| s.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_members_and_selection() {
+        let root = std::env::temp_dir().join(format!("rasm-workspace-{}", std::process::id()));
+        for dir in ["hello", "fibonacci", "scratch", "common"] {
+            fs::create_dir_all(root.join("examples").join(dir)).unwrap();
+        }
+        for dir in ["hello", "fibonacci", "scratch"] {
+            fs::write(root.join("examples").join(dir).join(".rasm.toml"), "").unwrap();
+        }
+        fs::write(root.join(".rasm.toml"), "[workspace]\nmembers = [\"examples/*\"]\n").unwrap();
+
+        let found = members(&root, &workspace(&["examples/*"], &["examples/scratch"])).unwrap();
+        let names: Vec<&str> = found.iter().map(|m| m.name.as_str()).collect();
+        assert_eq!(names, vec!["fibonacci", "hello"]);
+
+        let picked = select(found.clone(), &["hello".to_string()]).unwrap();
+        assert_eq!(picked, vec![found[1].clone()]);
+        let err = select(found, &["helo".to_string()]).unwrap_err().to_string();
+        assert!(err.contains("did you mean `hello`?"), "{}", err);
+
+        assert!(members(&root, &workspace(&["examples/common"], &[])).is_err());
+        assert!(members(&root, &workspace(&["examples/missing"], &[])).is_err());
+
+        let member_config = root.join("examples/hello/.rasm.toml");
+        let workspace_config = find_root(member_config.to_str().unwrap()).unwrap().unwrap();
+        assert_eq!(
+            Path::new(&workspace_config).canonicalize().unwrap(),
+            root.join(".rasm.toml").canonicalize().unwrap()
+        );
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn test_combine_member_summaries() {
+        let member = |name: &str| Member {
+            name: format!("{}-{}", name, std::process::id()),
+            dir: PathBuf::from(format!("./examples/{}", name)),
+        };
+        let (hello, fibonacci, broken) = (member("hello"), member("fibonacci"), member("broken"));
+
+        let mut warning = Diagnostic::new(Severity::Warning, "careful");
+        warning.file = Some("src/main.s".to_string());
+        let built = Summary {
+            files: vec![report::Step { name: "src/main.s".to_string(), duration_ms: 1.0, failed: false }],
+            links: vec![report::Step { name: "target/rasm/dev/hello".to_string(), duration_ms: 2.0, failed: false }],
+            diagnostics: vec![warning],
+            duration_ms: 3.0,
+        };
+        fs::write(summary_file(&hello), serde_json::to_string(&built).unwrap()).unwrap();
+
+        let combined = combine_summaries(&[(&hello, true), (&fibonacci, true), (&broken, false)]);
+        assert_eq!(combined.files[0].name, "examples/hello/src/main.s");
+        assert_eq!(combined.links[0].name, "examples/hello/target/rasm/dev/hello");
+        assert_eq!(combined.diagnostics[0].file.as_deref(), Some("examples/hello/src/main.s"));
+        assert_eq!(combined.diagnostics.len(), 2);
+        assert_eq!(combined.diagnostics[1].severity, Severity::Error);
+        assert!(combined.diagnostics[1].message.contains(&broken.name), "{}", combined.diagnostics[1].message);
+        assert!(!summary_file(&hello).exists());
+    }
+}